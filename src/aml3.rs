@@ -1,5 +1,8 @@
 pub mod parser;
 
+mod check;
+pub use check::warnings;
+
 mod command;
 pub use command::Aml3Command;
 
@@ -12,6 +15,9 @@ pub use expr::Aml3Expr;
 mod meta;
 pub use meta::Aml3Meta;
 
+mod pattern;
+pub use pattern::Aml3Pattern;
+
 mod scope;
 pub use scope::Aml3Scope;
 
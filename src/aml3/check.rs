@@ -0,0 +1,175 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::tokens::{AmvmType, Command, CommandExpression, CommandPattern, VariableKind};
+
+/// Variable and alias types known where a command is.
+#[derive(Clone, Default)]
+struct Known {
+    /// Declared types of variables, only known for function arguments
+    variables: HashMap<Box<str>, AmvmType>,
+    aliases: HashMap<Box<str>, AmvmType>,
+}
+
+impl Known {
+    fn forget(&mut self, name: &str) {
+        self.variables.remove(name);
+    }
+
+    /// Replace type aliases with the type they refer to.
+    fn resolve(&self, ty: &AmvmType) -> AmvmType {
+        let mut ty = ty;
+        let mut visited = HashSet::new();
+        while let Some(alias) = ty.name().and_then(|name| self.aliases.get(name)) {
+            if !visited.insert(ty.name()) {
+                break;
+            }
+            ty = alias;
+        }
+
+        ty.clone()
+    }
+}
+
+/// Warnings about code that parses fine but likely doesn't do what's
+/// expected. Only looks at the code, so it's done once when compiling.
+pub fn warnings(commands: &[Command]) -> Vec<String> {
+    let mut warnings = vec![];
+    visit(commands, Known::default(), &mut warnings);
+
+    warnings
+}
+
+fn visit(commands: &[Command], mut known: Known, warnings: &mut Vec<String>) {
+    let mut pos = None;
+
+    for cmd in commands {
+        match cmd {
+            Command::Meta { pos: at, .. } => pos = Some(*at),
+            Command::DeclareVariable { name, value, .. } => {
+                visit_expr(value, &known, warnings);
+                known.forget(name);
+            }
+            Command::Type { name, ty } => {
+                known.aliases.insert(name.clone(), ty.clone());
+            }
+            Command::Function {
+                name, args, body, ..
+            } => {
+                known.forget(name);
+                visit_function(args, body, &known, warnings);
+            }
+            Command::Match { value, arms } => {
+                if let CommandExpression::Var(name) = value {
+                    let ty = known.variables.get(name.as_str());
+                    if let Some(ty @ AmvmType::Union(..)) = ty.map(|ty| known.resolve(ty)).as_ref()
+                    {
+                        check_exhaustive(ty, arms, pos, warnings);
+                    }
+                }
+
+                for (pattern, body) in arms {
+                    let mut known = known.clone();
+                    if let CommandPattern::Struct(_, fields) = pattern {
+                        for (_, var) in fields {
+                            known.forget(var);
+                        }
+                    }
+                    visit(body, known, warnings);
+                }
+            }
+            Command::For { var, body, .. } => {
+                let mut known = known.clone();
+                known.forget(var);
+                visit(body, known, warnings);
+            }
+            Command::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                visit(body, known.clone(), warnings);
+                if let Some((var, body)) = catch {
+                    let mut known = known.clone();
+                    known.forget(var);
+                    visit(body, known, warnings);
+                }
+                if let Some(body) = finally {
+                    visit(body, known.clone(), warnings);
+                }
+            }
+            Command::Conditional {
+                body, otherwise, ..
+            } => {
+                visit(body, known.clone(), warnings);
+                if let Some(otherwise) = otherwise {
+                    visit(otherwise, known.clone(), warnings);
+                }
+            }
+            Command::Loop { body, .. } | Command::Scope { body } | Command::While { body, .. } => {
+                visit(body, known.clone(), warnings)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Anonymous functions are only looked for where they're declared.
+fn visit_expr(value: &CommandExpression, known: &Known, warnings: &mut Vec<String>) {
+    if let CommandExpression::Function(args, _, body) = value {
+        visit_function(args, body, known, warnings);
+    }
+}
+
+fn visit_function(
+    args: &[(Box<str>, VariableKind, AmvmType)],
+    body: &[Command],
+    known: &Known,
+    warnings: &mut Vec<String>,
+) {
+    let mut known = known.clone();
+    for (name, _, ty) in args {
+        known.variables.insert(name.clone(), ty.clone());
+    }
+
+    visit(body, known, warnings);
+}
+
+fn union_members(ty: &AmvmType) -> Vec<&AmvmType> {
+    match ty {
+        AmvmType::Union(a, b) => {
+            let mut members = union_members(a);
+            members.extend(union_members(b));
+            members
+        }
+        ty => vec![ty],
+    }
+}
+
+fn check_exhaustive(
+    ty: &AmvmType,
+    arms: &[(CommandPattern, Vec<Command>)],
+    pos: Option<(u16, u16)>,
+    warnings: &mut Vec<String>,
+) {
+    if arms
+        .iter()
+        .any(|(pattern, _)| matches!(pattern, CommandPattern::Wildcard))
+    {
+        return;
+    }
+
+    for member in union_members(ty) {
+        let is_covered = arms.iter().any(|(pattern, _)| match pattern {
+            CommandPattern::Type(ty) | CommandPattern::Struct(ty, _) => ty == member,
+            _ => false,
+        });
+
+        if !is_covered {
+            let at = pos.map_or(String::new(), |(line, _)| format!(" at line {line}"));
+            warnings.push(format!(
+                "Non-exhaustive match{at}, {} is not covered",
+                member.flat_name()
+            ));
+        }
+    }
+}
@@ -1,8 +1,8 @@
 use crate::aml3::Aml3Meta;
 use crate::{
-    aml3::{Aml3Expr, Aml3Scope, Aml3Struct, Aml3Type, Aml3Variable},
+    aml3::{Aml3Expr, Aml3Pattern, Aml3Scope, Aml3Struct, Aml3Type, Aml3Variable},
     parser::{self, Parser, ParserResult},
-    tokens::{Command, CommandExpression, VariableKind},
+    tokens::{AmvmType, Command, CommandExpression, VariableKind},
 };
 
 pub struct Aml3Command;
@@ -22,6 +22,80 @@ impl Aml3Command {
         Ok((parser, args))
     }
 
+    /// Arguments of a function, until the `{` of its body.
+    /// `$a #u8 $b mut #string`
+    pub fn visit_fn_args(
+        parser: Parser<'_>,
+    ) -> ParserResult<'_, Vec<(Box<str>, VariableKind, AmvmType)>> {
+        let mut parser_out = parser;
+        let mut args = vec![];
+
+        loop {
+            let (parser, _) = parser::char(' ')(parser_out)?;
+            let (_, c) = parser::anychar(parser)?;
+
+            match c {
+                '$' => {
+                    tracing::trace!(arg_index = args.len());
+
+                    let (parser, name) = parser::needs_space(Aml3Variable::visit)(parser)?;
+                    tracing::trace!(?name);
+                    let name: Box<str> = name.into();
+
+                    let (parser, kind) = if let Some('#' | '+') = parser.peek(0) {
+                        (parser, VariableKind::Const)
+                    } else {
+                        let (parser, kind) = parser::needs_space(parser::take_until_space)(parser)?;
+                        let kind = kind.value;
+                        let kind = VariableKind::from_str(kind).ok_or_else(|| {
+                            parser.error(
+                                parser::VerboseErrorKind::Context("Unknown argument kind"),
+                                true,
+                            )
+                        })?;
+
+                        (parser, kind)
+                    };
+                    tracing::trace!(?kind);
+
+                    let (parser, ty) = Aml3Type::visit(parser)?;
+                    tracing::trace!(?ty);
+
+                    args.push((name, kind, ty));
+
+                    parser_out = parser;
+                }
+                '{' => break,
+                _ => return Err(parser.error(parser::VerboseErrorKind::Char('$'), true)),
+            }
+        }
+
+        Ok((parser_out, args))
+    }
+
+    /// Optional label of a loop. `'outer`
+    fn visit_label(parser: Parser<'_>) -> ParserResult<'_, Option<Box<str>>> {
+        if parser.peek(0) != Some('\'') {
+            return Ok((parser, None));
+        }
+
+        let (parser, _) = parser::char('\'')(parser)?;
+        let (parser, label) = parser::take_until_space(parser)
+            .map_err(parser.nom_err_with_context("Expected label"))?;
+
+        Ok((parser, Some(Box::from(label.value))))
+    }
+
+    /// Label of `@break` or `@continue`, they can be the last thing in a line.
+    fn visit_jump_label(parser: Parser<'_>) -> ParserResult<'_, Option<Box<str>>> {
+        if parser.peek(0) != Some(' ') || parser.peek(1) != Some('\'') {
+            return Ok((parser, None));
+        }
+
+        let (parser, _) = parser::char(' ')(parser)?;
+        Self::visit_label(parser)
+    }
+
     pub fn visit_conditional(parser: Parser<'_>) -> ParserResult<'_, Command> {
         let (parser, condition) = Aml3Expr::visit(parser)?;
         let (parser, _) = parser::char(' ')(parser)?;
@@ -62,15 +136,74 @@ impl Aml3Command {
         ))
     }
 
+    /// `@try { ... } @catch $e { ... } @finally { ... }`, both handlers are optional
+    pub fn visit_try(parser: Parser<'_>) -> ParserResult<'_, Command> {
+        let (mut parser, body) = Aml3Scope::visit(parser, true)?;
+        let mut catch = None;
+        let mut finally = None;
+
+        loop {
+            let (parser_, _) = parser::opt(parser::char(' '))(parser)?;
+            let Ok((parser_, _)) = parser::char::<_, ()>('@')(parser_) else {
+                break;
+            };
+            let (parser_, handler) = parser::take_until_space(parser_)
+                .map_err(parser_.nom_err_with_context("Unexpected EOF"))?;
+            let (parser_, _) = parser::char(' ')(parser_)?;
+
+            match handler.value {
+                "catch" if catch.is_none() && finally.is_none() => {
+                    let (parser_, name) = parser::needs_space(Aml3Variable::visit)(parser_)?;
+                    let (parser_, body) = Aml3Scope::visit(parser_, true)?;
+
+                    catch = Some((Box::from(name), body));
+                    parser = parser_;
+                }
+                "finally" if finally.is_none() => {
+                    let (parser_, body) = Aml3Scope::visit(parser_, true)?;
+
+                    finally = Some(body);
+                    parser = parser_;
+                }
+                _ => {
+                    return Err(parser_.error(
+                        parser::VerboseErrorKind::Context("Expected 'catch' or 'finally' command"),
+                        true,
+                    ))
+                }
+            }
+        }
+
+        Ok((
+            parser,
+            Command::Try {
+                body,
+                catch,
+                finally,
+            },
+        ))
+    }
+
     fn visit_command(parser__: Parser<'_>) -> ParserResult<'_, Command> {
         let (parser_, cmd) = parser::take_until_space(parser__)
             .map_err(parser__.nom_err_with_context("Expected command"))?;
 
         tracing::trace!(?cmd.value);
 
+        match cmd.value {
+            "break" => {
+                let (parser, label) = Self::visit_jump_label(parser_)?;
+                return Ok((parser, Command::Break { label }));
+            }
+            "continue" => {
+                let (parser, label) = Self::visit_jump_label(parser_)?;
+                return Ok((parser, Command::Continue { label }));
+            }
+            _ => {}
+        }
+
         let (parser, _) = parser::char(' ')(parser_)?;
         match cmd.value {
-            "break" => Ok((parser_, Command::Break)),
             "builtin" => {
                 let (parser, name) = parser::take_until_space(parser)?;
                 let (parser, args) = Self::visit_args(parser)?;
@@ -91,6 +224,19 @@ impl Aml3Command {
                 Ok((parser, Command::Call { name, args }))
             }
 
+            "spawn" => {
+                let (parser, name) = Aml3Expr::visit(parser)?;
+                let (parser, args) = Self::visit_args(parser)?;
+
+                Ok((parser, Command::Spawn { name, args }))
+            }
+
+            "await" => {
+                let (parser, value) = Aml3Expr::visit(parser)?;
+
+                Ok((parser, Command::Await { value }))
+            }
+
             "declare" => {
                 let (parser, kind) = if let Some('$') = parser.peek(0) {
                     (parser, VariableKind::Const)
@@ -122,12 +268,38 @@ impl Aml3Command {
                 ))
             }
 
+            "enum" => {
+                let (parser, name) = Aml3Type::visit_name(parser)?;
+                let (parser, _) = parser::char(' ')(parser)?;
+                let (parser, variants) = Aml3Struct::visit_variants_block(parser)?;
+
+                let name = Box::from(name.unwrap_or_default());
+                let variants = variants
+                    .into_iter()
+                    .map(|(variant, fields)| {
+                        let fields = fields.into_iter().map(|v| (Box::from(v.0), v.1)).collect();
+                        (Box::from(variant), fields)
+                    })
+                    .collect();
+
+                Ok((parser, Command::Enum { name, variants }))
+            }
+
             "if" => Self::visit_conditional(parser),
 
             "loop" => {
+                let (parser, label) = Self::visit_label(parser)?;
+                let (parser, _) = parser::cond(label.is_some(), parser::char(' '))(parser)?;
                 let (parser, body) = Aml3Scope::visit(parser, true)?;
 
-                Ok((parser, Command::Loop { body }))
+                Ok((parser, Command::Loop { label, body }))
+            }
+
+            "match" => {
+                let (parser, value) = parser::needs_space(Aml3Expr::visit)(parser)?;
+                let (parser, arms) = Aml3Pattern::visit_arms(parser)?;
+
+                Ok((parser, Command::Match { value, arms }))
             }
 
             "puts" => {
@@ -143,16 +315,41 @@ impl Aml3Command {
             }
 
             "struct" => {
-                let (parser, name) = parser::needs_space(Aml3Type::visit_name)(parser)?;
+                let (parser, name) = Aml3Type::visit_name(parser)?;
+                let (parser, generics) = parser::needs_space(Aml3Type::visit_generics)(parser)?;
+                let (parser, inherits) = parser::opt(parser::delimited(
+                    parser::pair(parser::char(':'), parser::char(' ')),
+                    parser::separated_list1(
+                        parser::pair(parser::char(','), parser::char(' ')),
+                        Aml3Type::visit,
+                    ),
+                    parser::char(' '),
+                ))(parser)?;
                 let (parser, def) = Aml3Struct::visit_decl_block(parser)?;
 
                 let name = Box::from(name.unwrap_or_default());
                 let body = def.into_iter().map(|v| (Box::from(v.0), v.1)).collect();
 
-                Ok((parser, Command::Struct { name, body }))
+                Ok((
+                    parser,
+                    Command::Struct {
+                        name,
+                        generics,
+                        inherits: inherits.unwrap_or_default(),
+                        body,
+                    },
+                ))
             }
 
             "fn" => {
+                let (parser, is_mutable) = parser::opt(parser::tuple((
+                    parser::char('m'),
+                    parser::char('u'),
+                    parser::char('t'),
+                    parser::char(' '),
+                )))(parser)?;
+                let is_mutable = is_mutable.is_some();
+
                 let (parser, ret) = parser::needs_space(Aml3Type::visit)(parser)?;
                 tracing::trace!(?ret);
 
@@ -160,58 +357,10 @@ impl Aml3Command {
                 tracing::trace!(?name);
                 let name: Box<str> = name.into();
 
-                let (parser, args) = {
-                    let mut parser_out = parser;
-                    let mut args = vec![];
-
-                    loop {
-                        let (parser, _) = parser::char(' ')(parser_out)?;
-                        let (_, c) = parser::anychar(parser)?;
-
-                        match c {
-                            '$' => {
-                                tracing::trace!(arg_index = args.len());
-
-                                let (parser, name) =
-                                    parser::needs_space(Aml3Variable::visit)(parser)?;
-                                tracing::trace!(?name);
-                                let name: Box<str> = name.into();
-
-                                let (parser, kind) = if let Some('#') = parser.peek(0) {
-                                    (parser, VariableKind::Const)
-                                } else {
-                                    let (parser, kind) =
-                                        parser::needs_space(parser::take_until_space)(parser)?;
-                                    let kind = kind.value;
-                                    let kind = VariableKind::from_str(kind).ok_or_else(|| {
-                                        parser.error(
-                                            parser::VerboseErrorKind::Context(
-                                                "Unknown argument kind",
-                                            ),
-                                            true,
-                                        )
-                                    })?;
-
-                                    (parser, kind)
-                                };
-                                tracing::trace!(?kind);
-
-                                let (parser, ty) = Aml3Type::visit(parser)?;
-                                tracing::trace!(?ty);
-
-                                args.push((name, kind, ty));
-
-                                parser_out = parser;
-                            }
-                            '{' => break,
-                            _ => {
-                                return Err(parser.error(parser::VerboseErrorKind::Char('$'), true))
-                            }
-                        }
-                    }
-
-                    (parser_out, args)
-                };
+                let (parser, generics) = Aml3Type::visit_generics(parser)?;
+                tracing::trace!(?generics);
+
+                let (parser, args) = Self::visit_fn_args(parser)?;
 
                 let (parser, _) = parser::char(' ')(parser)?;
                 let (parser, body) = Aml3Scope::visit(parser, true)?;
@@ -221,6 +370,8 @@ impl Aml3Command {
                     parser,
                     Command::Function {
                         name,
+                        is_mutable,
+                        generics,
                         args,
                         ret,
                         body,
@@ -229,6 +380,8 @@ impl Aml3Command {
             }
 
             "for" => {
+                let (parser, label) = Self::visit_label(parser)?;
+                let (parser, _) = parser::cond(label.is_some(), parser::char(' '))(parser)?;
                 let (parser, var) = parser::needs_space(Aml3Variable::visit)(parser)?;
                 let (parser, iterator) = parser::needs_space(Aml3Expr::visit)(parser)?;
                 let (parser, body) = Aml3Scope::visit(parser, true)?;
@@ -236,6 +389,7 @@ impl Aml3Command {
                 Ok((
                     parser,
                     Command::For {
+                        label,
                         var: var.into(),
                         iterator,
                         body,
@@ -243,6 +397,93 @@ impl Aml3Command {
                 ))
             }
 
+            "static" => {
+                let (parser, name) = parser::needs_space(Aml3Variable::visit)(parser)?;
+                let (parser, value) = Aml3Expr::visit(parser)?;
+
+                Ok((
+                    parser,
+                    Command::Static {
+                        name: name.into(),
+                        value,
+                    },
+                ))
+            }
+
+            "while" => {
+                let (parser, label) = Self::visit_label(parser)?;
+                let (parser, _) = parser::cond(label.is_some(), parser::char(' '))(parser)?;
+                let (parser, condition) = parser::needs_space(Aml3Expr::visit)(parser)?;
+                let (parser, body) = Aml3Scope::visit(parser, true)?;
+
+                Ok((
+                    parser,
+                    Command::While {
+                        label,
+                        condition,
+                        body,
+                        is_do: false,
+                    },
+                ))
+            }
+
+            "do" => {
+                let (parser, label) = Self::visit_label(parser)?;
+                let (parser, _) = parser::cond(label.is_some(), parser::char(' '))(parser)?;
+                let (parser, body) = Aml3Scope::visit(parser, true)?;
+
+                let (parser, _) = parser::char(' ')(parser)?;
+                let (parser, _) = parser::char('@')(parser)?;
+                let (parser_, keyword) = parser::take_until_space(parser)
+                    .map_err(parser.nom_err_with_context("Expected 'while' command"))?;
+                if keyword.value != "while" {
+                    return Err(parser.error(
+                        parser::VerboseErrorKind::Context("Expected 'while' command"),
+                        true,
+                    ));
+                }
+
+                let (parser, _) = parser::char(' ')(parser_)?;
+                let (parser, condition) = Aml3Expr::visit(parser)?;
+
+                Ok((
+                    parser,
+                    Command::While {
+                        label,
+                        condition,
+                        body,
+                        is_do: true,
+                    },
+                ))
+            }
+
+            "throw" => {
+                let (parser, value) = Aml3Expr::visit(parser)?;
+
+                Ok((parser, Command::Throw { value }))
+            }
+
+            "try" => Self::visit_try(parser),
+
+            "yield" => {
+                let (parser, value) = Aml3Expr::visit(parser)?;
+
+                Ok((parser, Command::Yield { value }))
+            }
+
+            "type" => {
+                let (parser, name) = parser::needs_space(Aml3Type::visit_name)(parser)?;
+                let (parser, ty) = Aml3Type::visit(parser)?;
+
+                Ok((
+                    parser,
+                    Command::Type {
+                        name: Box::from(name.unwrap_or_default()),
+                        ty,
+                    },
+                ))
+            }
+
             "ret" => {
                 let (parser, expr) = Aml3Expr::visit(parser)?;
 
@@ -1,7 +1,7 @@
 use crate::{
-    aml3::{Aml3Struct, Aml3Type, Aml3Value, Aml3Variable},
+    aml3::{Aml3Command, Aml3Scope, Aml3Struct, Aml3Type, Aml3Value, Aml3Variable},
     parser::{self, Parser, ParserResult},
-    tokens::{BinaryKind, CommandExpression, VariableKind},
+    tokens::{AmvmType, BinaryKind, CommandExpression, Value, VariableKind},
 };
 
 pub struct Aml3Expr;
@@ -42,11 +42,68 @@ impl Aml3Expr {
 
         match kind {
             '+' => impl_op!(@binary consumed_parser, Add),
-            '-' => impl_op!(@binary consumed_parser, Sub),
+            '-' if consumed_parser.peek(0) == Some(' ') => impl_op!(@binary consumed_parser, Sub),
+            // -$x
+            '-' => {
+                let (parser, value) = Aml3Expr::visit(consumed_parser)?;
+
+                Ok((parser, CommandExpression::Negate(value.into())))
+            }
+            '*' if consumed_parser.peek(0) == Some('*') => {
+                let (parser, _) = parser::char('*')(consumed_parser)?;
+                impl_op!(@binary parser, Pow)
+            }
             '*' => impl_op!(@binary consumed_parser, Mult),
+            '/' => impl_op!(@binary consumed_parser, Div),
+            '%' => impl_op!(@binary consumed_parser, Mod),
+            '^' => impl_op!(@binary consumed_parser, BitXor),
+            '|' if consumed_parser.peek(0) == Some('|') => {
+                let (parser, _) = parser::char('|')(consumed_parser)?;
+                impl_op!(@single parser, Or)
+            }
+            '|' => impl_op!(@binary consumed_parser, BitOr),
+            '&' if consumed_parser.peek(0) == Some('&') => {
+                let (parser, _) = parser::char('&')(consumed_parser)?;
+                impl_op!(@single parser, And)
+            }
+            // `& $x` is a reference, so the bitwise and is `&. $a $b`
+            '&' if consumed_parser.peek(0) == Some('.') => {
+                let (parser, _) = parser::char('.')(consumed_parser)?;
+                impl_op!(@binary parser, BitAnd)
+            }
 
             '#' => {
                 let (parser, name) = Aml3Type::visit(parser)?;
+
+                // #fn #u8 $x #u8 { ... }
+                if name == AmvmType::Named(Box::from("fn")) {
+                    let (parser, ret) =
+                        parser::preceded(parser::char(' '), Aml3Type::visit)(parser)?;
+                    let (parser, args) = Aml3Command::visit_fn_args(parser)?;
+                    let (parser, _) = parser::char(' ')(parser)?;
+                    let (parser, body) = Aml3Scope::visit(parser, true)?;
+
+                    return Ok((parser, CommandExpression::Function(args, ret, body)));
+                }
+
+                // #Enum.Variant { ... }
+                if let Ok((parser, _)) = parser::char::<_, ()>('.')(parser) {
+                    let (parser, variant) = Aml3Variable::visit_ident(parser)?;
+                    let (parser, decl) =
+                        if parser.peek(0) == Some(' ') && parser.peek(1) == Some('{') {
+                            let (parser, _) = parser::char(' ')(parser)?;
+                            Aml3Struct::visit_def_block(parser)?
+                        } else {
+                            (parser, vec![])
+                        };
+                    let decl = decl.into_iter().map(|v| (Box::from(v.0), v.1)).collect();
+
+                    return Ok((
+                        parser,
+                        CommandExpression::Variant(name, Box::from(variant), decl),
+                    ));
+                }
+
                 let (parser, _) = parser::char(' ')(parser)?;
                 let (parser, decl) = Aml3Struct::visit_def_block(parser)?;
                 let decl = decl.into_iter().map(|v| (Box::from(v.0), v.1)).collect();
@@ -54,6 +111,64 @@ impl Aml3Expr {
                 Ok((parser, CommandExpression::Struct(name, decl)))
             }
 
+            '(' => {
+                let (parser, items) = parser::separated_list1(
+                    parser::pair(parser::char(','), parser::char(' ')),
+                    Aml3Expr::visit,
+                )(consumed_parser)?;
+                let (parser, _) = parser::char(')')(parser)?;
+
+                Ok((parser, CommandExpression::Tuple(items)))
+            }
+
+            '[' => {
+                let (parser, items) =
+                    parser::separated_list0(parser::char(' '), Aml3Expr::visit)(consumed_parser)?;
+                let (parser, _) = parser::char(']')(parser)?;
+
+                // Lists without expressions are stored as a single value
+                let values = items
+                    .iter()
+                    .map(|item| match item {
+                        CommandExpression::Value(value) => Some(value.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<Value>>>();
+
+                match values {
+                    Some(values) => Ok((parser, CommandExpression::Value(Value::List(values)))),
+                    None => Ok((parser, CommandExpression::List(items))),
+                }
+            }
+
+            '{' => {
+                let (parser, _) = parser::opt(parser::char(' '))(consumed_parser)?;
+                let (parser, entries) = if parser.peek(0) == Some('}') {
+                    (parser, vec![])
+                } else {
+                    // { KEY => VALUE, KEY => VALUE }
+                    let (parser, entries) = parser::separated_list1(
+                        parser::pair(parser::char(','), parser::char(' ')),
+                        parser::separated_pair(
+                            Aml3Expr::visit,
+                            parser::tuple((
+                                parser::char(' '),
+                                parser::char('='),
+                                parser::char('>'),
+                                parser::char(' '),
+                            )),
+                            Aml3Expr::visit,
+                        ),
+                    )(parser)?;
+                    let (parser, _) = parser::char(' ')(parser)?;
+
+                    (parser, entries)
+                };
+                let (parser, _) = parser::char('}')(parser)?;
+
+                Ok((parser, CommandExpression::Map(entries)))
+            }
+
             '$' => {
                 let (parser, var) = Aml3Variable::visit(parser)?;
                 Ok((parser, CommandExpression::Var(var.to_owned())))
@@ -61,7 +176,8 @@ impl Aml3Expr {
 
             '&' => {
                 let (parser, kind) = if consumed_parser.peek(0) == Some(' ') {
-                    (consumed_parser, VariableKind::Const)
+                    let (parser, _) = parser::char(' ')(consumed_parser)?;
+                    (parser, VariableKind::Const)
                 } else {
                     let (parser, kind) =
                         parser::needs_space(parser::take_until_space)(consumed_parser)?;
@@ -82,8 +198,24 @@ impl Aml3Expr {
 
             '_' => Ok((consumed_parser, CommandExpression::Prev)),
 
+            // is $value #Enum.Variant
+            'i' if consumed_parser.peek(0) == Some('s') && consumed_parser.peek(1) == Some(' ') => {
+                let (parser, _) = parser::char('s')(consumed_parser)?;
+                let (parser, _) = parser::char(' ')(parser)?;
+                let (parser, value) = Aml3Expr::visit(parser)?;
+                let (parser, _) = parser::char(' ')(parser)?;
+                let (parser, ty) = Aml3Type::visit(parser)?;
+                let (parser, _) = parser::char('.')(parser)?;
+                let (parser, variant) = Aml3Variable::visit_ident(parser)?;
+
+                Ok((
+                    parser,
+                    CommandExpression::IsVariant(value.into(), ty, Box::from(variant)),
+                ))
+            }
+
             // Possible two character operator
-            '!' | '>' | '<' | '=' | '.' => {
+            '!' | '>' | '<' | '=' | '.' | '?' => {
                 let parser = consumed_parser;
                 let (consumed_parser, second_kind) = parser::anychar(parser)
                     .map_err(parser.nom_err_with_context("Expected operator"))?;
@@ -91,19 +223,54 @@ impl Aml3Expr {
                 tracing::trace!(expr.char = ?format!("{kind}{second_kind}"));
 
                 match (kind, second_kind) {
+                    ('>', '>') => impl_op!(@binary consumed_parser, Shr),
                     ('>', '=') => impl_op!(@binary consumed_parser, GreaterThanEqual),
                     ('>', _) => impl_op!(@binary parser, GreaterThan),
 
+                    ('<', '<') => impl_op!(@binary consumed_parser, Shl),
                     ('<', '=') => impl_op!(@binary consumed_parser, LessThanEqual),
                     ('<', _) => impl_op!(@binary parser, LessThan),
 
                     ('=', '=') => impl_op!(@binary consumed_parser, Equal),
                     ('!', '=') => impl_op!(@binary consumed_parser, NotEqual),
-                    // TODO:
-                    // ('!', ' ') => impl_op!(@single parser, Negate),
-                    ('.', '.') => impl_op!(@single consumed_parser, Range),
+                    ('!', ' ') => {
+                        let (parser, value) = Aml3Expr::visit(consumed_parser)?;
+
+                        Ok((parser, CommandExpression::Not(value.into())))
+                    }
+                    // .. $from $to [$step] or ..= $from $to [$step]
+                    ('.', '.') => {
+                        let inclusive = consumed_parser.peek(0) == Some('=');
+                        let (parser, _) =
+                            parser::cond(inclusive, parser::char('='))(consumed_parser)?;
+                        let (parser, _) = parser::char(' ')(parser)?;
+                        let (parser, from) = Aml3Expr::visit(parser)?;
+                        let (parser, _) = parser::char(' ')(parser)?;
+                        let (parser, to) = Aml3Expr::visit(parser)?;
+
+                        // `@for $i .. 0u8 10u8 {` has no step
+                        let has_step = parser.peek(0) == Some(' ')
+                            && !matches!(parser.peek(1), None | Some('{' | ';' | '\n' | '\r'));
+                        let (parser, step) = parser::cond(
+                            has_step,
+                            parser::preceded(parser::char(' '), Aml3Expr::visit),
+                        )(parser)?;
+
+                        Ok((
+                            parser,
+                            CommandExpression::Range(
+                                from.into(),
+                                to.into(),
+                                step.map(Box::new),
+                                inclusive,
+                            ),
+                        ))
+                    }
                     ('.', _) => impl_op!(@single parser, Property),
 
+                    ('?', '.') => impl_op!(@single consumed_parser, OptionalProperty),
+                    ('?', '?') => impl_op!(@single consumed_parser, Coalesce),
+
                     _ => Err(parser.error(
                         parser::VerboseErrorKind::Context("Expected boolean operator"),
                         true,
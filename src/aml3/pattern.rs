@@ -0,0 +1,104 @@
+use crate::{
+    aml3::{Aml3Scope, Aml3Type, Aml3Value, Aml3Variable},
+    parser::{self, Parser, ParserResult},
+    tokens::{Command, CommandPattern},
+};
+
+pub struct Aml3Pattern;
+
+impl Aml3Pattern {
+    fn visit_fields(parser: Parser<'_>) -> ParserResult<'_, Vec<(Box<str>, Box<str>)>> {
+        let (parser, _) = parser::char('{')(parser)?;
+
+        let mut fields = vec![];
+
+        let mut parser = parser;
+        loop {
+            let (_parser, _) = parser::char(' ')(parser)?;
+            parser = _parser;
+
+            if let Ok((_parser, _)) = parser::char::<_, ()>('}')(parser) {
+                parser = _parser;
+                break;
+            }
+
+            let (_parser, field) = parser::needs_space(Aml3Variable::visit_ident)(parser)?;
+            let (_parser, var) = Aml3Variable::visit(_parser)?;
+            parser = _parser;
+
+            fields.push((Box::from(field), Box::from(var)));
+        }
+
+        Ok((parser, fields))
+    }
+
+    pub fn visit(parser: Parser<'_>) -> ParserResult<'_, CommandPattern> {
+        match parser.peek(0) {
+            Some('_') => {
+                let (parser, _) = parser::char('_')(parser)?;
+                Ok((parser, CommandPattern::Wildcard))
+            }
+            Some('#') => {
+                let (parser, ty) = Aml3Type::visit(parser)?;
+
+                if parser.peek(1) == Some('{') {
+                    let (parser, _) = parser::char(' ')(parser)?;
+                    let (parser, fields) = Self::visit_fields(parser)?;
+
+                    Ok((parser, CommandPattern::Struct(ty, fields)))
+                } else {
+                    Ok((parser, CommandPattern::Type(ty)))
+                }
+            }
+            _ => {
+                let (parser, value) = Aml3Value::visit(parser)?;
+                Ok((parser, CommandPattern::Value(value)))
+            }
+        }
+    }
+
+    /// Arms of a `@match` command
+    pub fn visit_arms(parser: Parser<'_>) -> ParserResult<'_, Vec<(CommandPattern, Vec<Command>)>> {
+        let (parser, _) = parser::char('{')(parser)?;
+
+        let mut arms = vec![];
+
+        let mut parser = parser;
+        loop {
+            if parser.value.is_empty() {
+                return Err(parser.error(parser::VerboseErrorKind::Context("Expected '}'"), true));
+            }
+
+            let value = parser::take_space::<_, ()>(parser).ok();
+
+            parser = if let Some((_parser, c)) = value {
+                parser = if c == '\n' {
+                    _parser.new_line()
+                } else {
+                    _parser
+                };
+
+                continue;
+            } else {
+                parser
+            };
+
+            let value = parser::char::<_, ()>('}')(parser).ok();
+            if let Some((_parser, _)) = value {
+                parser = _parser;
+                break;
+            }
+
+            let (_parser, pattern) = Self::visit(parser)?;
+            let (_parser, _) = parser::char(' ')(_parser)?;
+            let (_parser, _) = parser::pair(parser::char('='), parser::char('>'))(_parser)?;
+            let (_parser, _) = parser::char(' ')(_parser)?;
+            let (_parser, body) = Aml3Scope::visit(_parser, true)?;
+            parser = _parser;
+
+            arms.push((pattern, body));
+        }
+
+        Ok((parser, arms))
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     aml3::{Aml3Expr, Aml3Variable},
     parser::{self, Parser, ParserResult},
-    tokens::{AmvmType, CommandExpression},
+    tokens::{AmvmType, CommandExpression, EnumVariant},
 };
 
 use super::Aml3Type;
@@ -55,6 +55,55 @@ impl Aml3Struct {
         Ok((parser, properties))
     }
 
+    /// Variants of an enum, each one with an optional block of fields.
+    /// `{ Circle { r #f32 } Empty }`
+    pub fn visit_variants_block(parser: Parser<'_>) -> ParserResult<'_, Vec<EnumVariant<&str>>> {
+        let (parser, _) = parser::char('{')(parser)?;
+
+        let mut variants = vec![];
+
+        let mut parser = parser;
+        loop {
+            if parser.value.is_empty() {
+                return Err(parser.error(parser::VerboseErrorKind::Context("Expected '}'"), true));
+            }
+
+            let value = parser::take_space::<_, ()>(parser).ok();
+
+            parser = if let Some((_parser, c)) = value {
+                parser = if c == '\n' {
+                    _parser.new_line()
+                } else {
+                    _parser
+                };
+
+                continue;
+            } else {
+                parser
+            };
+
+            let value = parser::char::<_, ()>('}')(parser).ok();
+            if let Some((_parser, _)) = value {
+                parser = _parser;
+                break;
+            }
+
+            let (_parser, name) = Aml3Variable::visit_ident(parser)?;
+            let (_parser, fields) = if _parser.peek(0) == Some(' ') && _parser.peek(1) == Some('{')
+            {
+                let (_parser, _) = parser::char(' ')(_parser)?;
+                Self::visit_decl_block(_parser)?
+            } else {
+                (_parser, vec![])
+            };
+            parser = _parser;
+
+            variants.push((name, fields));
+        }
+
+        Ok((parser, variants))
+    }
+
     pub fn visit_def_block(parser: Parser<'_>) -> ParserResult<'_, Vec<(&str, CommandExpression)>> {
         let (parser, _) = parser::char('{')(parser)?;
 
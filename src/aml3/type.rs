@@ -1,19 +1,66 @@
 use crate::parser::{self, Parser, ParserResult};
 use crate::tokens::{AmvmPrimitiveType, AmvmType};
 
-use super::Aml3Variable;
-
 pub struct Aml3Type;
 
 impl Aml3Type {
+    fn visit_ident(parser: Parser<'_>) -> ParserResult<'_, &str> {
+        let (parser, name) = parser::is_not(" \t\r\n<>,=()[]?.")(parser)?;
+
+        Ok((parser, name.value))
+    }
+
     pub fn visit_name(parser: Parser<'_>) -> ParserResult<'_, Option<&str>> {
         let (parser, _) = parser::char('#')(parser)?;
 
-        let Ok((parser, name)) = parser::take_until_space::<_, ()>(parser) else {
+        let Ok((parser, name)) = Self::visit_ident(parser) else {
             return Ok((parser, None));
         };
 
-        Ok((parser, Some(name.value)))
+        Ok((parser, Some(name)))
+    }
+
+    /// Generic parameters of a declaration. `<T, U=#u8>`
+    pub fn visit_generics(
+        parser: Parser<'_>,
+    ) -> ParserResult<'_, Vec<(Box<str>, Option<AmvmType>)>> {
+        if parser.peek(0) != Some('<') {
+            return Ok((parser, vec![]));
+        }
+
+        let (parser, generics) = parser::delimited(
+            parser::char('<'),
+            parser::separated_list1(
+                parser::char(','),
+                parser::preceded(
+                    parser::opt(parser::char(' ')),
+                    parser::pair(
+                        Self::visit_ident,
+                        parser::opt(parser::preceded(parser::char('='), Self::visit)),
+                    ),
+                ),
+            ),
+            parser::char('>'),
+        )(parser)?;
+
+        let generics = generics
+            .into_iter()
+            .map(|(name, default)| (Box::from(name), default))
+            .collect();
+
+        Ok((parser, generics))
+    }
+
+    /// Arguments of a generic type. `<#u8, #string>`
+    fn visit_generic_args(parser: Parser<'_>) -> ParserResult<'_, Vec<AmvmType>> {
+        parser::delimited(
+            parser::char('<'),
+            parser::separated_list1(
+                parser::char(','),
+                parser::preceded(parser::opt(parser::char(' ')), Self::visit),
+            ),
+            parser::char('>'),
+        )(parser)
     }
 
     pub fn visit_tuple(parser: Parser<'_>) -> ParserResult<'_, AmvmType> {
@@ -30,6 +77,14 @@ impl Aml3Type {
         Ok((parser, AmvmType::Tuple(types)))
     }
 
+    pub fn visit_list(parser: Parser<'_>) -> ParserResult<'_, AmvmType> {
+        // [#A]
+        let (parser, ty) =
+            parser::delimited(parser::char('['), Self::visit, parser::char(']'))(parser)?;
+
+        Ok((parser, AmvmType::List(Box::new(ty))))
+    }
+
     pub fn visit(parser: Parser<'_>) -> ParserResult<'_, AmvmType> {
         if let Ok((parser, _)) = parser::char::<_, ()>('+')(parser) {
             let (parser, _) = parser::char(' ')(parser)?;
@@ -40,19 +95,29 @@ impl Aml3Type {
             return Ok((parser, AmvmType::Union(Box::new(a), Box::new(b))));
         }
 
+        let (parser, _) = parser::char('#')(parser)?;
         let (_parser, c) = parser::anychar(parser)?;
 
         let (parser, curr_type) = match c {
             '(' => Self::visit_tuple(parser)?,
+            '[' => Self::visit_list(parser)?,
             _ => {
-                let (parser, name) = Aml3Variable::visit_ident(parser)
-                    .map_or_else(|_| (parser, None), |v| (v.0, Some(v.1)));
+                let (parser, name) =
+                    Self::visit_ident(parser).map_or_else(|_| (parser, None), |v| (v.0, Some(v.1)));
 
                 if let Some(name) = name {
-                    let type_ = match name {
-                        "string" => AmvmType::Primitive(AmvmPrimitiveType::String),
-                        "u8" => AmvmType::Primitive(AmvmPrimitiveType::U8),
-                        _ => AmvmType::Named(Box::from(name)),
+                    let (parser, type_) = if parser.peek(0) == Some('<') {
+                        let (parser, args) = Self::visit_generic_args(parser)?;
+                        (parser, AmvmType::Generic(Box::from(name), args))
+                    } else {
+                        let type_ = match name {
+                            "bool" => AmvmType::Primitive(AmvmPrimitiveType::Bool),
+                            "string" => AmvmType::Primitive(AmvmPrimitiveType::String),
+                            "u8" => AmvmType::Primitive(AmvmPrimitiveType::U8),
+                            _ => AmvmType::Named(Box::from(name)),
+                        };
+
+                        (parser, type_)
                     };
 
                     (parser, type_)
@@ -62,6 +127,11 @@ impl Aml3Type {
             }
         };
 
+        // #u8?
+        if let Ok((parser, _)) = parser::char::<_, ()>('?')(parser) {
+            return Ok((parser, AmvmType::Nullable(Box::new(curr_type))));
+        }
+
         Ok((parser, curr_type))
     }
 }
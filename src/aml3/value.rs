@@ -21,7 +21,7 @@ impl Aml3Value {
 
             match b {
                 'u' | 'i' => {
-                    let (parser, size) = parser::take_until_space(parser)
+                    let (parser, size) = parser::take_until_delimiter(parser)
                         .map_err(parser.nom_err_with_context("Expected number size"))?;
 
                     return match size.value {
@@ -39,6 +39,27 @@ impl Aml3Value {
                                 todo!("Number: Integer 8")
                             }
                         }
+                        "16" if b == 'i' => {
+                            let value = str.parse::<i16>().map_err(|_| {
+                                parser.error(
+                                    parser::VerboseErrorKind::Context("Can't parse number"),
+                                    true,
+                                )
+                            })?;
+
+                            Ok((parser, Value::I16(value)))
+                        }
+
+                        "size" if b == 'u' => {
+                            let value = str.parse::<usize>().map_err(|_| {
+                                parser.error(
+                                    parser::VerboseErrorKind::Context("Can't parse number"),
+                                    true,
+                                )
+                            })?;
+
+                            Ok((parser, Value::Usize(value)))
+                        }
 
                         _ => Err(parser.error(
                             parser::VerboseErrorKind::Context("Unknown number size"),
@@ -146,7 +167,7 @@ impl Aml3Value {
     }
 
     fn visit_bool(parser: Parser<'_>) -> ParserResult<'_, Value> {
-        let (parser, value) = parser::take_until_space(parser)
+        let (parser, value) = parser::take_until_delimiter(parser)
             .map_err(parser.nom_err_with_context("Unexpected EOF"))?;
 
         match value.value {
@@ -160,6 +181,17 @@ impl Aml3Value {
         }
     }
 
+    fn visit_null(parser: Parser<'_>) -> ParserResult<'_, Value> {
+        let (parser, value) = parser::take_until_delimiter(parser)
+            .map_err(parser.nom_err_with_context("Unexpected EOF"))?;
+
+        match value.value {
+            "null" => Ok((parser, Value::Null)),
+
+            _ => Err(parser.error(parser::VerboseErrorKind::Context("Expected null"), true)),
+        }
+    }
+
     pub fn visit(parser: Parser<'_>) -> ParserResult<'_, Value> {
         let first = parser.peek(0).ok_or_else(|| {
             parser.error(parser::VerboseErrorKind::Context("Unexpected EOF"), false)
@@ -171,6 +203,7 @@ impl Aml3Value {
             '\'' => Self::visit_char(parser),
 
             't' | 'f' => Self::visit_bool(parser),
+            'n' => Self::visit_null(parser),
 
             _ => Err(parser.error(parser::VerboseErrorKind::Context("Unknown value"), true)),
         }
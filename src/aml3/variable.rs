@@ -4,7 +4,7 @@ pub struct Aml3Variable;
 
 impl Aml3Variable {
     pub fn visit_ident(parser: Parser<'_>) -> ParserResult<'_, &str> {
-        let (parser, name) = parser::take_until_space(parser)
+        let (parser, name) = parser::is_not(" \t\r\n<,)]")(parser)
             .map_err(parser.nom_err_with_context("Expected a space after a variable name"))?;
 
         Ok((parser, name.value))
@@ -1,5 +1,6 @@
 use std::fmt::Write;
 use std::path::Path;
+use std::time::Duration;
 
 use amvm::{parser::Parser, runtime::*, tokens::*, *};
 
@@ -18,8 +19,55 @@ fn read_source(source: impl AsRef<Path> + std::fmt::Display) -> Result<String, S
         .map_err(|err| format!("Can't read file {source}\nCause by: {err}"))
 }
 
+/// Message of a thrown value that wasn't caught.
+fn uncaught(value: &Value) -> String {
+    match value {
+        Value::Object(ValueObject::Instance(_, fields)) => match fields.get("message") {
+            Some(message) => message.read().unwrap().to_string_or_default(),
+            None => value.to_string(),
+        },
+        Value::Object(_) => value.to_string(),
+        value => value.to_string_or_default(),
+    }
+}
+
+/// Runtime options given as `--name=value` before the file.
+fn runtime_options(
+    args: impl Iterator<Item = String>,
+) -> Result<(RuntimeOptions, impl Iterator<Item = String>), String> {
+    let mut options = RuntimeOptions::default();
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next_if(|arg| arg.starts_with("--")) {
+        let Some((name, value)) = arg.split_once('=') else {
+            return Err(format!("Expected a value for {arg}"));
+        };
+        let number = value
+            .parse::<u64>()
+            .map_err(|_| format!("Expected a number for {name}, found {value:?}"))?;
+
+        match name {
+            "--max-call-depth" => options.max_call_depth = number as usize,
+            "--fuel" => options.fuel = Some(number),
+            "--timeout" => options.timeout = Some(Duration::from_millis(number)),
+            "--max-heap" => options.max_heap = Some(number as usize),
+            "--max-variables" => options.max_variables = Some(number as usize),
+            _ => return Err(format!("Unknown option {name}")),
+        }
+    }
+
+    Ok((options, args))
+}
+
 fn parse_aml3(content: &str, source: impl std::fmt::Display) -> Result<Vec<Command>, String> {
-    aml3::from_str(&content).map_err(|err| format!("Can't parse file {source}\n{err}"))
+    let commands =
+        aml3::from_str(&content).map_err(|err| format!("Can't parse file {source}\n{err}"))?;
+
+    for warning in aml3::warnings(&commands) {
+        eprintln!("Warning: {warning}");
+    }
+
+    Ok(commands)
 }
 
 fn compile(mut args: impl Iterator<Item = String>) -> Result<(), String> {
@@ -40,40 +88,81 @@ fn compile(mut args: impl Iterator<Item = String>) -> Result<(), String> {
     Ok(())
 }
 
-fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
-    let source_file = args.next().expect("Provide file path to the bytecode file");
-    let source = std::fs::read_to_string(&source_file)
-        .map_err(|err| format!("Can't read file {source_file}\nCause by: {err}"))?;
-    let parser = Parser::new(&source, &true);
-    let (_, program) = Program::visit(parser).map_err(Parser::flat_errors)?;
-
-    let mut runtime = program.runtime(source_file.into());
-    runtime.run().map_err(|err| match err {
-        AmvmPropagate::Err(err) => err.to_string(),
-        AmvmPropagate::Return(_) => "Returning outside function scope".to_owned(),
-        AmvmPropagate::Break => "Breaking outside loop scope".to_owned(),
-    })?;
-
-    Ok(())
+/// Calls are evaluated recursively, so programs run on a thread with room
+/// for the deepest calls the options allow.
+fn with_stack<F>(stack_size: usize, f: F) -> Result<(), String>
+where
+    F: FnOnce() -> Result<(), String> + Send + 'static,
+{
+    std::thread::Builder::new()
+        .name(String::from("amvm"))
+        .stack_size(stack_size)
+        .spawn(f)
+        .map_err(|err| {
+            format!("Can't start the program with a stack of {stack_size} bytes\nCause by: {err}")
+        })?
+        .join()
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
 }
 
-fn jit(mut args: impl Iterator<Item = String>) -> Result<(), String> {
-    let source = next_args_source(&mut args)?;
-    let content = read_source(&source)?;
-    let commands: Vec<Command> = parse_aml3(&content, &source)?;
-
-    let header = AmvmHeader {
-        sum_kind: AmvmTypeCasting::TypeCastingStrictlessString,
-    };
-    let program = Program::new(header, commands);
-    let mut runtime = program.runtime(source.into());
-    runtime.run().map_err(|err| match err {
-        AmvmPropagate::Err(err) => err.to_string(),
-        AmvmPropagate::Return(_) => "Returning outside function scope".to_owned(),
-        AmvmPropagate::Break => "Breaking outside loop scope".to_owned(),
-    })?;
+fn run(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let (options, args) = runtime_options(args)?;
+    let mut args = args.collect::<Vec<_>>().into_iter();
+
+    with_stack(options.stack_size(), move || {
+        let source_file = args.next().expect("Provide file path to the bytecode file");
+        let source = std::fs::read_to_string(&source_file)
+            .map_err(|err| format!("Can't read file {source_file}\nCause by: {err}"))?;
+        let parser = Parser::new(&source, &true);
+        let (_, program) = Program::visit(parser).map_err(Parser::flat_errors)?;
+
+        let mut runtime = program.runtime(source_file.into()).with_options(options);
+        runtime.run().map_err(|err| match err {
+            AmvmPropagate::Err(err) => err.to_string(),
+            AmvmPropagate::Return(_) | AmvmPropagate::TailCall(..) => {
+                "Returning outside function scope".to_owned()
+            }
+            AmvmPropagate::Break(_) => "Breaking outside loop scope".to_owned(),
+            AmvmPropagate::Continue(_) => "Continuing outside loop scope".to_owned(),
+            AmvmPropagate::Throw(value, err) => {
+                err.report(&format!("Uncaught exception: {}", uncaught(&value)))
+            }
+            AmvmPropagate::Limit(limit) => format!("Execution stopped: {limit}"),
+        })?;
+
+        Ok(())
+    })
+}
 
-    Ok(())
+fn jit(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let (options, args) = runtime_options(args)?;
+    let mut args = args.collect::<Vec<_>>().into_iter();
+
+    with_stack(options.stack_size(), move || {
+        let source = next_args_source(&mut args)?;
+        let content = read_source(&source)?;
+        let commands: Vec<Command> = parse_aml3(&content, &source)?;
+
+        let header = AmvmHeader {
+            sum_kind: AmvmTypeCasting::TypeCastingStrictlessString,
+        };
+        let program = Program::new(header, commands);
+        let mut runtime = program.runtime(source.into()).with_options(options);
+        runtime.run().map_err(|err| match err {
+            AmvmPropagate::Err(err) => err.to_string(),
+            AmvmPropagate::Return(_) | AmvmPropagate::TailCall(..) => {
+                "Returning outside function scope".to_owned()
+            }
+            AmvmPropagate::Break(_) => "Breaking outside loop scope".to_owned(),
+            AmvmPropagate::Continue(_) => "Continuing outside loop scope".to_owned(),
+            AmvmPropagate::Throw(value, err) => {
+                err.report(&format!("Uncaught exception: {}", uncaught(&value)))
+            }
+            AmvmPropagate::Limit(limit) => format!("Execution stopped: {limit}"),
+        })?;
+
+        Ok(())
+    })
 }
 
 fn inspect(mut args: impl Iterator<Item = String>) -> Result<(), String> {
@@ -120,6 +209,12 @@ fn help() {
     println!("  jit [source]               Compile aml3 and run it");
     println!("  aml3 [source]              Parse and show info about aml3");
     println!("  run [filepath]             Execute the bytecode file at filepath");
+    println!("\nOptions of jit and run, before the file:");
+    println!("  --max-call-depth=N         Nested calls before a stack overflow");
+    println!("  --fuel=N                   Commands and expressions that can run");
+    println!("  --timeout=MS               Time the program can run for");
+    println!("  --max-heap=BYTES           Estimated memory the variables can use");
+    println!("  --max-variables=N          Variables that can be alive at once");
 }
 
 fn main() {
@@ -1,3 +1,7 @@
+// Values and contexts hold `Rc`s and raw pointers, they never leave the
+// thread that runs the program.
+#![allow(clippy::arc_with_non_send_sync)]
+
 pub mod aml3;
 mod error;
 mod macros;
@@ -467,6 +467,16 @@ where
     is_not(" \t\r\n")(parser)
 }
 
+/// Like [take_until_space] but also stops at the end of an item inside
+/// a tuple or list literal.
+pub fn take_until_delimiter<I, Err>(parser: I) -> IResult<I, I, Err>
+where
+    I: InputTakeAtPosition<Item = char>,
+    Err: ParseError<I>,
+{
+    is_not(" \t\r\n,)]")(parser)
+}
+
 /// Specify that should be a space after the parser success.
 /// Example:
 /// ```rust
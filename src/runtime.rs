@@ -3,19 +3,26 @@ use std::rc::Rc;
 use std::sync::Mutex;
 use std::{collections::HashMap, sync::Arc};
 
-use crate::tokens::{AmvmHeader, AmvmScope, AmvmTypeDefinition, Command, Value};
+use crate::tokens::{AmvmHeader, AmvmScope, AmvmType, AmvmTypeDefinition, Command, Value};
 
 mod commands;
 pub mod core;
 mod error;
 mod expr;
+pub mod limits;
+mod machine;
+mod options;
 mod result;
 mod scope;
+pub mod tasks;
+pub mod types;
 pub mod variable;
 
 pub use error::AmvmError;
 pub use expr::AmvmExprResult;
-pub use result::{AmvmPropagate, AmvmResult};
+pub use limits::AmvmLimit;
+pub use options::RuntimeOptions;
+pub use result::{AmvmPropagate, AmvmResult, AmvmTailCall};
 pub use variable::AmvmVariable;
 
 const PREV_MAX: usize = u8::MAX as usize;
@@ -26,6 +33,13 @@ pub struct Context {
     prev: Vec<AmvmExprResult>,
 
     structs: HashMap<String, AmvmTypeDefinition>,
+    aliases: HashMap<String, AmvmType>,
+
+    /// Context of a call to a const function, captured variables
+    /// can't be modified from it.
+    is_const_call: bool,
+    /// Static variables of the mutable function being called.
+    state: Option<Arc<Mutex<Context>>>,
 
     parent: Option<Arc<Mutex<Context>>>,
 }
@@ -41,7 +55,10 @@ impl Context {
         Self {
             variables: Default::default(),
             structs: Default::default(),
+            aliases: Default::default(),
             prev: Vec::with_capacity(PREV_MAX),
+            is_const_call: false,
+            state: None,
             parent: None,
         }
     }
@@ -50,7 +67,10 @@ impl Context {
         Self {
             variables: Default::default(),
             structs: Default::default(),
+            aliases: Default::default(),
             prev: Vec::with_capacity(PREV_MAX),
+            is_const_call: false,
+            state: None,
             parent: Some(Arc::clone(&this)),
         }
     }
@@ -71,6 +91,10 @@ impl Context {
         self.variables
             .get(name)
             .cloned()
+            .or_else(|| {
+                let state = self.state.as_ref()?.lock().unwrap();
+                state.variables.get(name).cloned()
+            })
             .or_else(|| {
                 self.parent
                     .as_ref()
@@ -87,6 +111,93 @@ impl Context {
                 std::process::exit(1)
             })
     }
+
+    /// Check if `name` is defined outside of the current call to a
+    /// const function.
+    pub fn is_captured(&self, name: &str) -> bool {
+        let is_static = self
+            .state
+            .as_ref()
+            .is_some_and(|state| state.lock().unwrap().variables.contains_key(name));
+        if self.variables.contains_key(name) || is_static {
+            return false;
+        }
+
+        if self.is_const_call {
+            return true;
+        }
+
+        self.parent
+            .as_ref()
+            .is_some_and(|p| p.lock().unwrap().is_captured(name))
+    }
+
+    /// Like [Self::is_captured], but for the variable itself, whatever
+    /// name it's reached by.
+    pub fn is_captured_variable(&self, variable: &AmvmVariable) -> bool {
+        if self.defines(variable, false) {
+            return false;
+        }
+
+        let Some(parent) = &self.parent else {
+            return false;
+        };
+
+        let parent = parent.lock().unwrap();
+        if self.is_const_call {
+            parent.defines(variable, true)
+        } else {
+            parent.is_captured_variable(variable)
+        }
+    }
+
+    /// Check if `variable` is defined in this context, or in any of its
+    /// parents with `is_recursive`.
+    fn defines(&self, variable: &AmvmVariable, is_recursive: bool) -> bool {
+        let is_static = self.state.as_ref().is_some_and(|state| {
+            let state = state.lock().unwrap();
+            state.variables.values().any(|v| v.ptr_eq(variable))
+        });
+        if is_static || self.variables.values().any(|v| v.ptr_eq(variable)) {
+            return true;
+        }
+
+        is_recursive
+            && self
+                .parent
+                .as_ref()
+                .is_some_and(|p| p.lock().unwrap().defines(variable, true))
+    }
+
+    /// Context with the static variables of the mutable function being called.
+    pub fn function_state(this: &Arc<Mutex<Context>>) -> Option<Arc<Mutex<Context>>> {
+        let context = this.lock().unwrap();
+        if let Some(state) = &context.state {
+            return Some(Arc::clone(state));
+        }
+
+        if context.is_const_call {
+            return None;
+        }
+
+        context.parent.as_ref().and_then(Context::function_state)
+    }
+
+    pub fn get_struct(&self, name: &str) -> Option<AmvmTypeDefinition> {
+        self.structs.get(name).cloned().or_else(|| {
+            self.parent
+                .as_ref()
+                .and_then(|p| p.lock().unwrap().get_struct(name))
+        })
+    }
+
+    pub fn get_alias(&self, name: &str) -> Option<AmvmType> {
+        self.aliases.get(name).cloned().or_else(|| {
+            self.parent
+                .as_ref()
+                .and_then(|p| p.lock().unwrap().get_alias(name))
+        })
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -101,19 +212,30 @@ impl Runtime {
         }
     }
 
+    pub fn with_options(mut self, options: RuntimeOptions) -> Self {
+        self.scope.options = Rc::new(options);
+        self
+    }
+
     fn registry_base_types(&self) {
         let structs = &mut self.scope.context.lock().unwrap().structs;
         structs.insert("Iterator".to_owned(), core::amvm_iterator_type());
+        structs.insert("Error".to_owned(), core::amvm_error_type());
+        structs.insert("Range".to_owned(), core::amvm_range_type());
+        structs.insert("Task".to_owned(), core::amvm_task_type());
     }
 
     pub fn run(&mut self) -> AmvmResult {
         self.registry_base_types();
+        let usage = &self.scope.usage;
+        usage.start(&self.scope.options);
+        usage.register(&self.scope.options, &self.scope.context);
 
         for cmd in self.scope.body.clone().iter() {
             commands::eval(&mut self.scope, cmd)?;
         }
 
-        Ok(Value::Null)
+        tasks::run_all(&mut self.scope)
     }
 }
 
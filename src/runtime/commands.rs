@@ -2,24 +2,38 @@ use std::rc::Rc;
 
 use crate::tokens::AmvmMeta;
 use crate::{
-    runtime::{expr, scope, AmvmPropagate, AmvmResult, AmvmVariable},
+    runtime::{expr, limits, scope, AmvmPropagate, AmvmResult, AmvmVariable},
     tokens::{AmvmScope, Command, Value},
 };
 
 mod assign_var;
+mod r#await;
 pub mod builtin;
 pub mod call;
-mod conditional;
-mod r#for;
-mod function;
-mod r#loop;
+pub mod conditional;
+mod r#enum;
+pub mod r#for;
+pub mod function;
+pub mod r#loop;
+pub mod r#match;
 mod puts;
+mod spawn;
+mod r#static;
 mod r#struct;
+pub mod r#try;
+mod r#type;
+pub mod r#while;
+pub mod r#yield;
 
 pub fn eval(scope: &mut AmvmScope, cmd: &Command) -> AmvmResult {
+    if !matches!(cmd, Command::Meta { .. }) {
+        limits::charge(scope)?;
+    }
+
     let out = match cmd {
         Command::AssignVariable { name, value } => assign_var::eval(scope, name, value),
-        Command::Break => Err(super::AmvmPropagate::Break),
+        Command::Await { value } => r#await::eval(scope, value),
+        Command::Break { label } => Err(AmvmPropagate::Break(label.clone())),
         Command::Builtin { name, args } => builtin::eval(scope, name, args),
         Command::Call { name, args } => call::eval(scope, name, args),
         Command::Conditional {
@@ -30,25 +44,34 @@ pub fn eval(scope: &mut AmvmScope, cmd: &Command) -> AmvmResult {
         Command::DeclareVariable { name, value, kind } => {
             let name = name.clone();
             let value = expr::eval(scope, value)?.as_value_ref();
+            let value = value.as_ref().clone().stored_in(&scope.context);
 
-            scope.context.lock().unwrap().variables.insert(
-                name.to_string(),
-                AmvmVariable::new(kind.clone(), value.as_ref().clone()),
-            );
+            scope
+                .context
+                .lock()
+                .unwrap()
+                .variables
+                .insert(name.to_string(), AmvmVariable::new(kind.clone(), value));
             Ok(Value::Null)
         }
+        Command::Enum { name, variants } => r#enum::eval(scope, name, variants),
+        Command::Continue { label } => Err(AmvmPropagate::Continue(label.clone())),
         Command::For {
+            label,
             var,
             iterator,
             body,
-        } => r#for::eval(scope, var, iterator, body),
+        } => r#for::eval(scope, label, var, iterator, body),
         Command::Function {
             name,
+            is_mutable,
+            generics,
             args,
             ret,
             body,
-        } => function::eval(scope, name, args, ret, body),
-        Command::Loop { body } => r#loop::eval(scope, body),
+        } => function::eval(scope, name, *is_mutable, generics, args, ret, body),
+        Command::Loop { label, body } => r#loop::eval(scope, label, body),
+        Command::Match { value, arms } => r#match::eval(scope, value, arms),
         Command::Meta { pos, code } => {
             scope.meta = Some(
                 AmvmMeta {
@@ -72,11 +95,35 @@ pub fn eval(scope: &mut AmvmScope, cmd: &Command) -> AmvmResult {
             Ok(Value::Null)
         }
         Command::Puts { value } => puts::eval(scope, value),
+        Command::Static { name, value } => r#static::eval(scope, name, value),
         Command::Return { value } => Err(AmvmPropagate::Return(
             expr::eval(scope, value)?.as_value().as_ref().clone(),
         )),
         Command::Scope { body } => scope::eval(scope, body, false),
-        Command::Struct { name, body } => r#struct::eval(scope, name, body),
+        Command::Struct {
+            name,
+            generics,
+            inherits,
+            body,
+        } => r#struct::eval(scope, name, generics, inherits, body),
+        Command::Throw { value } => Err(AmvmPropagate::Throw(
+            Box::new(expr::eval(scope, value)?.as_value().as_ref().clone()),
+            Box::new(scope.error("Uncaught exception")),
+        )),
+        Command::Try {
+            body,
+            catch,
+            finally,
+        } => r#try::eval(scope, body, catch, finally),
+        Command::Type { name, ty } => r#type::eval(scope, name, ty),
+        Command::While {
+            label,
+            condition,
+            body,
+            is_do,
+        } => r#while::eval(scope, label, condition, body, *is_do),
+        Command::Yield { value } => r#yield::eval(scope, value),
+        Command::Spawn { name, args } => spawn::eval(scope, name, args),
     };
 
     // Remove meta after each command, except for meta
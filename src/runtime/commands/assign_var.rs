@@ -1,5 +1,5 @@
 use crate::{
-    runtime::{expr, AmvmResult},
+    runtime::{expr, AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, CommandExpression, Value},
 };
 
@@ -7,9 +7,25 @@ pub fn eval(scope: &mut AmvmScope, name: &str, value: &CommandExpression) -> Amv
     let value = expr::eval(scope, value)?.as_value();
 
     let context = scope.context.lock().unwrap();
+    if context.is_captured(name) {
+        drop(context);
+        return Err(AmvmPropagate::Err(
+            scope.error("Const functions can't modify captured variables"),
+        ));
+    }
+
+    // Only declared here, the function can't be stored in the context
+    // where it was defined through its parents
+    let value = value.as_ref().clone();
+    let value = if context.variables.contains_key(name) {
+        value.stored_in(&scope.context)
+    } else {
+        value
+    };
+
     let variable = context.get_variable(&name.to_owned());
     drop(context);
-    _ = variable.assign(scope, value.as_ref().clone())?;
+    _ = variable.assign(scope, value)?;
 
     Ok(Value::Null)
 }
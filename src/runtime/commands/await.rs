@@ -0,0 +1,13 @@
+use crate::{
+    runtime::{expr, tasks, AmvmResult},
+    tokens::{AmvmScope, CommandExpression, Value},
+};
+
+pub fn eval(scope: &mut AmvmScope, value: &CommandExpression) -> AmvmResult {
+    let value = expr::eval(scope, value)?.as_value();
+    let result = tasks::wait(scope, &value)?;
+
+    scope.context.lock().unwrap().push_prev_value(result);
+
+    Ok(Value::Null)
+}
@@ -1,5 +1,6 @@
 use std::io::Write;
 use std::sync::Arc;
+use std::time::Duration;
 
 use expr::AmvmExprResult;
 use variable::AmvmVariable;
@@ -7,11 +8,36 @@ use variable::AmvmVariable;
 use crate::runtime::variable;
 use crate::tokens::VariableKind;
 use crate::{
-    runtime::{expr, AmvmPropagate, AmvmResult},
-    tokens::{AmvmScope, CommandExpression, Value, ValueObject},
+    runtime::{expr, tasks, AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, CommandExpression, Value, ValueMap, ValueMapKey, ValueObject},
 };
 
+/// Builtins that change the variable given as their first argument
+const MUTATING: &[&str] = &[
+    ".io.stdin.read_line",
+    ".obj.mut_access",
+    ".list.push",
+    ".list.pop",
+    ".list.set",
+    ".map.set",
+    ".map.delete",
+    ".mem.replace",
+];
+
 pub fn eval(scope: &mut AmvmScope, name: &str, args: &[CommandExpression]) -> AmvmResult {
+    if MUTATING.contains(&name) {
+        let is_captured = args
+            .first()
+            .and_then(root_variable)
+            .is_some_and(|var| scope.context.lock().unwrap().is_captured(var));
+
+        if is_captured {
+            return Err(AmvmPropagate::Err(
+                scope.error("Const functions can't modify captured variables"),
+            ));
+        }
+    }
+
     let mut args_evaluated = Vec::with_capacity(args.len());
 
     for arg in args {
@@ -123,7 +149,9 @@ pub fn call(
 
             let res = match variable {
                 ValueObject::Native(_) => todo!("Can't get properties of native object"),
-                ValueObject::Instance(_, map) | ValueObject::PropertyMap(map) => match &*field {
+                ValueObject::Instance(_, map)
+                | ValueObject::PropertyMap(map)
+                | ValueObject::Variant(_, _, map) => match &*field {
                     Value::String(name) => {
                         let Some(v) = map.get(name) else {
                             drop(args);
@@ -147,6 +175,174 @@ pub fn call(
             return Ok(Some(res));
         }
 
+        // LIST //
+        ".list.push" => {
+            let mut args = args.iter();
+            let list = args.next().expect("Should use `.list.push $list VALUE`");
+            let value = args.next().expect("Should use `.list.push $list VALUE`");
+            let value = value.as_value();
+
+            with_list(scope, &list.as_ref(), |list| {
+                list.push(value.as_ref().clone())
+            })?;
+        }
+        ".list.pop" => {
+            let list = args.first().expect("Should use `.list.pop $list`");
+            let value = with_list(scope, &list.as_ref(), |list| list.pop())?;
+
+            return Ok(Some(value.unwrap_or(Value::Null).into()));
+        }
+        ".list.set" => {
+            let mut args = args.iter();
+            let list = args
+                .next()
+                .expect("Should use `.list.set $list INDEX VALUE`");
+            let index = args
+                .next()
+                .expect("Should use `.list.set $list INDEX VALUE`");
+            let value = args
+                .next()
+                .expect("Should use `.list.set $list INDEX VALUE`");
+            let value = value.as_value();
+
+            let Some(index) = index.as_value().as_index() else {
+                return Err(AmvmPropagate::Err(
+                    scope.error("List index should be a number"),
+                ));
+            };
+
+            let is_set = with_list(scope, &list.as_ref(), |list| {
+                list.get_mut(index)
+                    .map(|item| *item = value.as_ref().clone())
+                    .is_some()
+            })?;
+
+            if !is_set {
+                return Err(AmvmPropagate::Err(scope.error("List index out of bounds")));
+            }
+        }
+        ".list.len" => {
+            let list = args.first().expect("Should use `.list.len $list`");
+            let len = list.with_value(|list| match list {
+                Value::List(list) => Some(list.len()),
+                _ => None,
+            });
+            let Some(len) = len else {
+                return Err(AmvmPropagate::Err(scope.error("Expected a list")));
+            };
+
+            return Ok(Some(Value::Usize(len).into()));
+        }
+        ".list.slice" => {
+            let mut args = args.iter();
+            let list = args.next().expect("Should use `.list.slice $list FROM TO`");
+            let from = args.next().expect("Should use `.list.slice $list FROM TO`");
+            let to = args.next().expect("Should use `.list.slice $list FROM TO`");
+
+            let (Some(from), Some(to)) = (from.as_value().as_index(), to.as_value().as_index())
+            else {
+                return Err(AmvmPropagate::Err(
+                    scope.error("List index should be a number"),
+                ));
+            };
+            let slice = list.with_value(|list| match list {
+                Value::List(list) => Some(list.get(from..to).map(<[Value]>::to_vec)),
+                _ => None,
+            });
+            let Some(slice) = slice else {
+                return Err(AmvmPropagate::Err(scope.error("Expected a list")));
+            };
+            let Some(slice) = slice else {
+                return Err(AmvmPropagate::Err(scope.error("List index out of bounds")));
+            };
+
+            return Ok(Some(Value::List(slice).into()));
+        }
+
+        // MAP //
+        ".map.get" => {
+            let mut args = args.iter();
+            let map = args.next().expect("Should use `.map.get $map KEY`");
+            let key = args.next().expect("Should use `.map.get $map KEY`");
+            let key = map_key(scope, &key.as_value())?;
+
+            let value = with_map_value(scope, map, |map| map.get(&key).cloned())?;
+
+            return Ok(Some(value.unwrap_or(Value::Null).into()));
+        }
+        ".map.set" => {
+            let mut args = args.iter();
+            let map = args.next().expect("Should use `.map.set $map KEY VALUE`");
+            let key = args.next().expect("Should use `.map.set $map KEY VALUE`");
+            let value = args.next().expect("Should use `.map.set $map KEY VALUE`");
+            let key = map_key(scope, &key.as_value())?;
+            let value = value.as_value();
+
+            with_map(scope, &map.as_ref(), |map| {
+                map.insert(key, value.as_ref().clone())
+            })?;
+        }
+        ".map.has" => {
+            let mut args = args.iter();
+            let map = args.next().expect("Should use `.map.has $map KEY`");
+            let key = args.next().expect("Should use `.map.has $map KEY`");
+            let key = map_key(scope, &key.as_value())?;
+
+            let has = with_map_value(scope, map, |map| map.contains_key(&key))?;
+
+            return Ok(Some(Value::Bool(has).into()));
+        }
+        ".map.delete" => {
+            let mut args = args.iter();
+            let map = args.next().expect("Should use `.map.delete $map KEY`");
+            let key = args.next().expect("Should use `.map.delete $map KEY`");
+            let key = map_key(scope, &key.as_value())?;
+
+            let value = with_map(scope, &map.as_ref(), |map| map.remove(&key))?;
+
+            return Ok(Some(value.unwrap_or(Value::Null).into()));
+        }
+        ".map.keys" => {
+            let map = args.first().expect("Should use `.map.keys $map`");
+            let keys = with_map_value(scope, map, |map| {
+                map.keys().map(ValueMapKey::to_value).collect()
+            })?;
+
+            return Ok(Some(Value::List(keys).into()));
+        }
+        ".map.len" => {
+            let map = args.first().expect("Should use `.map.len $map`");
+            let len = with_map_value(scope, map, ValueMap::len)?;
+
+            return Ok(Some(Value::Usize(len).into()));
+        }
+
+        // INT //
+        ".int.u8" => {
+            let value = args.first().expect("Should use `.int.u8 VALUE`");
+            let value = to_int::<u8>(scope, &value.as_value())?;
+
+            return Ok(Some(Value::U8(value).into()));
+        }
+        ".int.i16" => {
+            let value = args.first().expect("Should use `.int.i16 VALUE`");
+            let value = to_int::<i16>(scope, &value.as_value())?;
+
+            return Ok(Some(Value::I16(value).into()));
+        }
+        ".int.usize" => {
+            let value = args.first().expect("Should use `.int.usize VALUE`");
+            let value = to_int::<usize>(scope, &value.as_value())?;
+
+            return Ok(Some(Value::Usize(value).into()));
+        }
+
+        // TIME //
+        ".time.sleep" => {
+            let duration = sleep_duration(scope, args)?;
+            tasks::sleep(scope, duration)?;
+        }
+
         // MEM //
         ".mem.replace" => {
             let mut args = args.iter();
@@ -164,3 +360,104 @@ pub fn call(
 
     Ok(None)
 }
+
+/// Variable that holds the value given by `expr`, if it comes from one.
+fn root_variable(expr: &CommandExpression) -> Option<&str> {
+    match expr {
+        CommandExpression::Var(name) => Some(name),
+        CommandExpression::Property(value, _)
+        | CommandExpression::OptionalProperty(value, _)
+        | CommandExpression::Ref(_, value) => root_variable(value),
+        _ => None,
+    }
+}
+
+/// Run `f` over the list stored in a mutable variable.
+fn with_list<T>(
+    scope: &mut AmvmScope,
+    variable: &AmvmVariable,
+    f: impl FnOnce(&mut Vec<Value>) -> T,
+) -> Result<T, AmvmPropagate> {
+    if !variable.is_mutable() {
+        return Err(AmvmPropagate::Err(
+            scope.error("Cannot borrow inmutable to mutable"),
+        ));
+    }
+
+    let mut variable = variable.write().expect("Checked above");
+    let Value::List(list) = &mut *variable else {
+        return Err(AmvmPropagate::Err(scope.error("Expected a list")));
+    };
+
+    Ok(f(list))
+}
+
+/// Run `f` over the map stored in a mutable variable.
+fn with_map<T>(
+    scope: &mut AmvmScope,
+    variable: &AmvmVariable,
+    f: impl FnOnce(&mut ValueMap) -> T,
+) -> Result<T, AmvmPropagate> {
+    if !variable.is_mutable() {
+        return Err(AmvmPropagate::Err(
+            scope.error("Cannot borrow inmutable to mutable"),
+        ));
+    }
+
+    let mut variable = variable.write().expect("Checked above");
+    let Value::Map(map) = &mut *variable else {
+        return Err(AmvmPropagate::Err(scope.error("Expected a map")));
+    };
+
+    Ok(f(map))
+}
+
+/// Run `f` over a map, without copying it.
+fn with_map_value<T>(
+    scope: &mut AmvmScope,
+    map: &AmvmExprResult,
+    f: impl FnOnce(&ValueMap) -> T,
+) -> Result<T, AmvmPropagate> {
+    map.with_value(|map| match map {
+        Value::Map(map) => Some(f(map)),
+        _ => None,
+    })
+    .ok_or_else(|| AmvmPropagate::Err(scope.error("Expected a map")))
+}
+
+fn map_key(scope: &mut AmvmScope, key: &Value) -> Result<ValueMapKey, AmvmPropagate> {
+    ValueMapKey::from_value(key).ok_or_else(|| {
+        AmvmPropagate::Err(scope.error("Map keys should be numbers, strings, chars or booleans"))
+    })
+}
+
+/// Convert between integer types, failing if the value doesn't fit.
+fn to_int<T>(scope: &mut AmvmScope, value: &Value) -> Result<T, AmvmPropagate>
+where
+    T: TryFrom<u8> + TryFrom<i16> + TryFrom<usize>,
+{
+    let value = match value {
+        Value::U8(v) => T::try_from(*v).ok(),
+        Value::I16(v) => T::try_from(*v).ok(),
+        Value::Usize(v) => T::try_from(*v).ok(),
+        Value::Ref(var) => return to_int(scope, &var.read()),
+        _ => return Err(AmvmPropagate::Err(scope.error("Expected an integer"))),
+    };
+
+    value.ok_or_else(|| AmvmPropagate::Err(scope.error("Number doesn't fit in the target type")))
+}
+
+/// Duration of a `.time.sleep`, from its arguments.
+pub fn sleep_duration(
+    scope: &mut AmvmScope,
+    args: &[AmvmExprResult],
+) -> Result<Duration, AmvmPropagate> {
+    let ms = args.first().expect("Should use `.time.sleep MILLISECONDS`");
+    let Some(ms) = ms.as_value().as_index() else {
+        return Err(AmvmPropagate::Err(
+            scope.error("Sleep duration should be a number of milliseconds"),
+        ));
+    };
+
+    Ok(Duration::from_millis(ms as u64))
+}
@@ -1,21 +1,38 @@
-use std::cell::RefCell;
 use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::runtime::AmvmVariable;
-use crate::tokens::{Command, VariableKind};
+use crate::runtime::{limits, types, AmvmTailCall, AmvmVariable};
+use crate::tokens::{AmvmFrame, AmvmMeta, AmvmType, VariableKind};
 use crate::{
     runtime::{expr, scope, AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, CommandExpression, Value, ValueFun},
 };
 
-pub fn eval(
+/// Name of the called function for its frame, from the expression that
+/// gives it.
+pub fn callee_name(name: &CommandExpression) -> Box<str> {
+    match name {
+        CommandExpression::Var(name) => Box::from(name.as_str()),
+        CommandExpression::Property(value, property)
+        | CommandExpression::OptionalProperty(value, property) => match &**property {
+            CommandExpression::Value(Value::String(property)) => {
+                Box::from(format!("{}.{property}", callee_name(value)))
+            }
+            _ => Box::from(format!("{}[]", callee_name(value))),
+        },
+        _ => Box::from("<anonymous>"),
+    }
+}
+
+/// Name, function and arguments of a `@call`.
+pub fn evaluate(
     scope: &mut AmvmScope,
     name: &CommandExpression,
     args: &[CommandExpression],
-) -> AmvmResult {
+) -> Result<(Box<str>, ValueFun, Vec<AmvmVariable>), AmvmPropagate> {
+    let callee = callee_name(name);
     let name = expr::eval(scope, name)?;
-    let name = name.as_value();
-    let Some(fun) = name.as_function() else {
+    let Some(fun) = name.as_value().as_function().cloned() else {
         return Err(AmvmPropagate::Err(scope.error("Calling to a non-function")));
     };
 
@@ -25,29 +42,188 @@ pub fn eval(
         args_evaluated.push(expr::eval(scope, arg)?.as_ref());
     }
 
-    let value = call(scope, fun, &args_evaluated)?;
+    Ok((callee, fun, args_evaluated))
+}
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    name: &CommandExpression,
+    args: &[CommandExpression],
+) -> AmvmResult {
+    let (callee, fun, args) = evaluate(scope, name, args)?;
+    let value = call(scope, &callee, &fun, &args)?;
 
     scope.context.lock().unwrap().push_prev_value(value);
 
     Ok(Value::Null)
 }
 
-enum Either<'a> {
-    Native(Rc<RefCell<dyn FnMut(&mut AmvmScope) -> AmvmResult>>),
-    Body(&'a Vec<Command>),
+/// Call `fun` from `scope`, `name` is how it's shown in backtraces.
+pub fn call(
+    scope: &mut AmvmScope,
+    name: &str,
+    fun: &ValueFun,
+    args: &[AmvmVariable],
+) -> AmvmResult {
+    let call_site = scope.meta.clone();
+    call_once(scope, name, call_site, fun, args, false)
 }
 
-pub fn call(scope: &mut AmvmScope, fun: &ValueFun, args: &[AmvmVariable]) -> AmvmResult {
-    let (named_args, body, mut inner) = match fun {
-        ValueFun::Native(a, _, b) => (a, Either::Native(b.clone()), scope.create_sub(vec![])),
-        ValueFun::Const(a, _, b) | ValueFun::Mutable(a, _, b) => {
-            (a, Either::Body(b), scope.create_sub(b.to_vec()))
+/// Do the tail calls given by a function body. Each one is called from
+/// `scope`, the caller of the first function, until one returns a value.
+/// They replace its frame, so they keep its call site.
+fn tail_calls(
+    scope: &mut AmvmScope,
+    call_site: Option<Rc<AmvmMeta>>,
+    mut tail_call: AmvmTailCall,
+) -> AmvmResult {
+    loop {
+        let AmvmTailCall {
+            name, fun, args, ..
+        } = tail_call;
+        let call_site = call_site.clone();
+
+        match call_once(scope, &name, call_site, &fun, &args, true) {
+            Err(AmvmPropagate::TailCall(next)) => tail_call = *next,
+            result => return result,
         }
-    };
+    }
+}
 
+/// Result of a body run outside of [call], where tail calls aren't done
+/// by the caller. They are called now and returned.
+pub fn finish_tail_call(scope: &mut AmvmScope, result: AmvmResult) -> AmvmResult {
+    match result {
+        Err(AmvmPropagate::TailCall(tail_call)) => {
+            let AmvmTailCall {
+                name,
+                call_site,
+                fun,
+                args,
+            } = *tail_call;
+
+            Err(AmvmPropagate::Return(call_once(
+                scope, &name, call_site, &fun, &args, false,
+            )?))
+        }
+        result => result,
+    }
+}
+
+/// Call `fun`, tail calls from its body are given back when `is_tail`,
+/// otherwise they are done before returning.
+fn call_once(
+    scope: &mut AmvmScope,
+    name: &str,
+    call_site: Option<Rc<AmvmMeta>>,
+    fun: &ValueFun,
+    args: &[AmvmVariable],
+    is_tail: bool,
+) -> AmvmResult {
+    let _call = limits::Call::enter(scope)?;
+    let (mut inner, mut generics) = prepare(scope, name, call_site, fun, args)?;
     let inner = &mut inner;
 
-    for (value, (name, arg_kind, _)) in args.iter().zip(named_args) {
+    match fun {
+        ValueFun::Native(_, _, fun) => (fun.borrow_mut())(inner),
+        ValueFun::Const(_, _, ret, body, _) | ValueFun::Mutable(_, _, ret, body, ..) => {
+            if super::r#yield::is_generator(body) {
+                return Ok(super::r#yield::generator(inner.clone(), ret, generics));
+            }
+
+            let result = match scope::eval(inner, body, true) {
+                Err(AmvmPropagate::TailCall(tail_call)) if !is_tail => {
+                    let call_site = inner.frame.as_ref().and_then(|f| f.call_site.clone());
+                    tail_calls(scope, call_site, *tail_call)
+                }
+                result => result,
+            };
+
+            finish(inner, result, Some(ret), &mut generics)
+        }
+    }
+}
+
+/// Value of a call once its body finished with `result`, checked against
+/// `ret` unless it's `None`.
+pub fn finish(
+    inner: &mut AmvmScope,
+    result: AmvmResult,
+    ret: Option<&AmvmType>,
+    generics: &mut types::AmvmGenerics,
+) -> AmvmResult {
+    let value = match result {
+        Ok(value) | Err(AmvmPropagate::Return(value)) => value,
+        Err(err) => return Err(outside_loop(inner, err)),
+    };
+
+    if let Some(ret) = ret {
+        check_return(inner, &value, ret, generics)?;
+    }
+
+    Ok(value)
+}
+
+/// Labels are lexical, so `@break` and `@continue` can't leave the body
+/// of a function to reach the loops of its caller.
+fn outside_loop(scope: &mut AmvmScope, err: AmvmPropagate) -> AmvmPropagate {
+    match err {
+        AmvmPropagate::Break(_) => AmvmPropagate::Err(scope.error("Breaking outside loop scope")),
+        AmvmPropagate::Continue(_) => {
+            AmvmPropagate::Err(scope.error("Continuing outside loop scope"))
+        }
+        err => err,
+    }
+}
+
+/// Scope where `fun` runs when called from `scope`, with its arguments
+/// declared, and the generics bound by them.
+pub fn prepare(
+    scope: &mut AmvmScope,
+    name: &str,
+    call_site: Option<Rc<AmvmMeta>>,
+    fun: &ValueFun,
+    args: &[AmvmVariable],
+) -> Result<(AmvmScope, types::AmvmGenerics), AmvmPropagate> {
+    let (mut generics, named_args, mut inner) = match fun {
+        ValueFun::Native(a, ..) => (types::AmvmGenerics::new(), a, scope.create_sub(vec![])),
+        ValueFun::Const(g, a, _, b, env) | ValueFun::Mutable(g, a, _, b, env, _) => {
+            let Some(env) = env.get() else {
+                return Err(AmvmPropagate::Err(
+                    scope.error("The context of the function was dropped"),
+                ));
+            };
+
+            (
+                types::generics_from_decl(g),
+                a,
+                scope.create_sub_from(b.to_vec(), &env),
+            )
+        }
+    };
+
+    let mut frame = AmvmFrame {
+        name: Box::from(name),
+        call_site,
+        args: Vec::with_capacity(args.len()),
+        parent: scope.frame.clone(),
+    };
+    inner.depth = scope.depth + 1;
+    {
+        let mut context = inner.context.lock().unwrap();
+        context.is_const_call = matches!(fun, ValueFun::Const(..));
+        if let ValueFun::Mutable(.., state) = fun {
+            context.state = Some(Arc::clone(state));
+        }
+    }
+
+    for (value, (name, arg_kind, arg_type)) in args.iter().zip(named_args) {
+        if !types::check(scope, &value.read(), arg_type, &mut generics) {
+            return Err(AmvmPropagate::Err(
+                scope.error("Argument doesn't match its declared type"),
+            ));
+        }
+
         let name = name.to_string();
         let value_kind = value.get_kind();
 
@@ -62,19 +238,27 @@ pub fn call(scope: &mut AmvmScope, fun: &ValueFun, args: &[AmvmVariable]) -> Amv
             AmvmVariable::from_rw(*arg_kind, value.get_rw().1)
         };
 
+        frame.args.push((Box::from(name.as_str()), arg.clone()));
+
         inner.context.lock().unwrap().variables.insert(name, arg);
     }
+    inner.frame = Some(Rc::new(frame));
 
-    match body {
-        Either::Body(body) => {
-            let value = match scope::eval(inner, body, true) {
-                Ok(v) => v,
-                Err(AmvmPropagate::Return(v)) => v,
-                Err(e) => return Err(e),
-            };
+    Ok((inner, generics))
+}
 
-            Ok(value)
-        }
-        Either::Native(ref fun) => (fun.borrow_mut())(inner),
+/// Check the value returned by a function body against its return type.
+fn check_return(
+    inner: &mut AmvmScope,
+    value: &Value,
+    ret: &AmvmType,
+    generics: &mut types::AmvmGenerics,
+) -> Result<(), AmvmPropagate> {
+    if !types::check(inner, value, ret, generics) {
+        return Err(AmvmPropagate::Err(
+            inner.error("Return value doesn't match its declared type"),
+        ));
     }
+
+    Ok(())
 }
@@ -1,5 +1,5 @@
 use crate::{
-    runtime::{expr, scope, AmvmResult},
+    runtime::{expr, scope, AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, Command, CommandExpression, Value},
 };
 
@@ -9,18 +9,29 @@ pub fn eval(
     body: &Vec<Command>,
     otherwise: &Option<Vec<Command>>,
 ) -> AmvmResult {
+    match branch(scope, condition, body, otherwise)? {
+        Some(body) => scope::eval(scope, body, false),
+        None => Ok(Value::Null),
+    }
+}
+
+/// Body that runs for the condition, `None` when there is nothing to run.
+pub fn branch<'a>(
+    scope: &mut AmvmScope,
+    condition: &CommandExpression,
+    body: &'a Vec<Command>,
+    otherwise: &'a Option<Vec<Command>>,
+) -> Result<Option<&'a Vec<Command>>, AmvmPropagate> {
     let condition = expr::eval(scope, condition)?.as_value();
     let Value::Bool(condition) = condition.as_ref() else {
-        return Err(crate::runtime::AmvmPropagate::Err(
+        return Err(AmvmPropagate::Err(
             scope.error("Condition should be boolean"),
         ));
     };
 
     if *condition {
-        scope::eval(scope, body, false)
-    } else if let Some(otherwise) = otherwise {
-        scope::eval(scope, otherwise, false)
+        Ok(Some(body))
     } else {
-        Ok(Value::Null)
+        Ok(otherwise.as_ref())
     }
 }
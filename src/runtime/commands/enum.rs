@@ -0,0 +1,19 @@
+use crate::{
+    runtime::AmvmResult,
+    tokens::{AmvmScope, AmvmTypeDefinition, EnumVariant, Value},
+};
+
+pub fn eval(scope: &mut AmvmScope, name: &str, variants: &[EnumVariant]) -> AmvmResult {
+    let declaration = AmvmTypeDefinition::Enum {
+        variants: variants.to_vec(),
+    };
+
+    scope
+        .context
+        .lock()
+        .unwrap()
+        .structs
+        .insert(name.to_string(), declaration);
+
+    Ok(Value::Null)
+}
@@ -1,64 +1,164 @@
+use std::rc::Rc;
+
+use super::r#loop::{self, Flow};
 use crate::runtime::AmvmPropagate;
 use crate::{
-    runtime::{expr, AmvmResult, AmvmVariable},
-    tokens::{AmvmScope, Command, CommandExpression, Value, VariableKind},
+    runtime::{expr, limits, scope, AmvmResult, AmvmVariable},
+    tokens::{AmvmScope, Command, CommandExpression, Value, ValueFun, VariableKind},
 };
 
-#[allow(unused)]
-pub fn eval(
-    scope: &mut AmvmScope,
-    var: &str,
-    iterator: &CommandExpression,
-    body: &Vec<Command>,
-) -> AmvmResult {
-    let iterator = expr::eval(scope, iterator)?.as_ref();
-    let iterate = expr::property::get(
-        scope,
-        &*iterator.read(),
-        &Value::String(String::from("next")),
-    )?;
-    let Some(iterate) = iterate.as_function() else {
-        return Err(AmvmPropagate::Err(
-            scope.error("Iterator next should be a function"),
-        ));
-    };
+/// Where the values of a `@for` come from.
+pub enum Source {
+    /// Built-in iterables, without going through an iterator
+    Values(expr::iter::Values),
+    /// Standard iterators give each item from `next`, before running the body
+    Standard {
+        iterator: AmvmVariable,
+        next: ValueFun,
+    },
+    /// Other iterators have the current item in `value`, and `next` is
+    /// called after running the body
+    Legacy {
+        iterator: AmvmVariable,
+        next: ValueFun,
+        is_first: bool,
+    },
+    Finished,
+}
 
-    'l: loop {
-        let scope = &mut scope.create_sub(body.clone());
+impl Source {
+    pub fn new(scope: &mut AmvmScope, iterator: &CommandExpression) -> Result<Self, AmvmPropagate> {
+        let iterator = expr::eval(scope, iterator)?.as_ref();
+        if let Some(values) = expr::iter::values(&iterator.read()) {
+            let values = values.map_err(|err| AmvmPropagate::Err(scope.error(err)))?;
+
+            return Ok(Self::Values(values));
+        }
 
-        let result_value = expr::property::get(
+        let next = expr::property::get(
             scope,
             &*iterator.read(),
-            &Value::String(String::from("value")),
+            &Value::String(String::from("next")),
         )?;
+        let Some(next) = next.as_function().cloned() else {
+            return Err(AmvmPropagate::Err(
+                scope.error("Iterator next should be a function"),
+            ));
+        };
 
-        scope.context.lock().unwrap().variables.insert(
-            var.to_string(),
-            AmvmVariable::new(VariableKind::Const, result_value),
-        );
-
-        for cmd in scope.body.clone().iter() {
-            match super::eval(scope, cmd) {
-                Err(crate::runtime::AmvmPropagate::Break) => break 'l,
-                Err(e) => return Err(e),
-                _ => {}
-            };
+        let done = expr::property::get(
+            scope,
+            &*iterator.read(),
+            &Value::String(String::from("done")),
+        )?;
+        match done {
+            Value::Bool(true) => Ok(Self::Finished),
+            Value::Bool(false) => Ok(Self::Standard { iterator, next }),
+            _ => Ok(Self::Legacy {
+                iterator,
+                next,
+                is_first: true,
+            }),
         }
+    }
 
-        let result = super::call::call(scope, iterate, &[iterator.clone()])?;
+    /// Value for the next run of the body, `None` once there are no more.
+    pub fn next(&mut self, scope: &mut AmvmScope) -> Result<Option<Value>, AmvmPropagate> {
+        match self {
+            Self::Values(values) => {
+                let value = values.next();
+                if value.is_some() {
+                    limits::charge(scope)?;
+                }
 
-        let Value::Bool(result_done) =
-            expr::property::get(scope, &result, &Value::String(String::from("done")))?
-        else {
-            return Err(AmvmPropagate::Err(scope.error(
-                "Iterator result should have the following structure: {{ done #bool value #T }}",
-            )));
-        };
+                Ok(value)
+            }
+            Self::Standard { iterator, next } => {
+                let result =
+                    super::call::call(scope, "next", next, std::slice::from_ref(iterator))?;
+                let (done, value) = read_result(scope, &result)?;
+
+                Ok((!done).then_some(value))
+            }
+            // Continuing still needs to advance the iterator
+            Self::Legacy {
+                iterator,
+                next,
+                is_first,
+            } => {
+                if !*is_first {
+                    let result =
+                        super::call::call(scope, "next", next, std::slice::from_ref(iterator))?;
+                    let (done, _) = read_result(scope, &result)?;
+                    if done {
+                        return Ok(None);
+                    }
+                }
+                *is_first = false;
+
+                let value = expr::property::get(
+                    scope,
+                    &*iterator.read(),
+                    &Value::String(String::from("value")),
+                )?;
 
-        if result_done {
-            break;
+                Ok(Some(value))
+            }
+            Self::Finished => Ok(None),
+        }
+    }
+}
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    label: &Option<Box<str>>,
+    var: &str,
+    iterator: &CommandExpression,
+    body: &[Command],
+) -> AmvmResult {
+    let mut source = Source::new(scope, iterator)?;
+    let body = Rc::new(body.to_vec());
+
+    while let Some(mut inner) = run(scope, &body, var, &mut source)? {
+        if let Flow::Exit(result) = r#loop::flow(label, scope::eval(&mut inner, &body, true)) {
+            return result;
         }
     }
 
     Ok(Value::Null)
 }
+
+/// Scope for a run of the body with the next value declared, `None` when
+/// there are no more.
+pub fn run(
+    scope: &mut AmvmScope,
+    body: &Rc<Vec<Command>>,
+    var: &str,
+    source: &mut Source,
+) -> Result<Option<AmvmScope>, AmvmPropagate> {
+    let Some(value) = source.next(scope)? else {
+        return Ok(None);
+    };
+
+    let inner = scope.create_sub_shared(body);
+    inner.context.lock().unwrap().variables.insert(
+        var.to_string(),
+        AmvmVariable::new(VariableKind::Const, value),
+    );
+
+    Ok(Some(inner))
+}
+
+/// `done` and `value` of the result of an iterator `next` function.
+fn read_result(scope: &mut AmvmScope, result: &Value) -> Result<(bool, Value), AmvmPropagate> {
+    let Value::Bool(done) =
+        expr::property::get(scope, result, &Value::String(String::from("done")))?
+    else {
+        return Err(AmvmPropagate::Err(scope.error(
+            "Iterator result should have the following structure: {{ done #bool value #T }}",
+        )));
+    };
+    let value = expr::property::get(scope, result, &Value::String(String::from("value")))?;
+
+    Ok((done, value))
+}
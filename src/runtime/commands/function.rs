@@ -1,19 +1,28 @@
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
 use crate::{
-    runtime::{AmvmResult, AmvmVariable},
-    tokens::{AmvmScope, AmvmType, Command, Value, ValueFun, VariableKind},
+    runtime::{AmvmResult, AmvmVariable, Context},
+    tokens::{AmvmScope, AmvmType, Command, FunEnv, Value, ValueFun, VariableKind},
 };
 
 pub fn eval(
     scope: &mut AmvmScope,
     name: &str,
-    args: &Vec<(Box<str>, VariableKind, AmvmType)>,
+    is_mutable: bool,
+    generics: &[(Box<str>, Option<AmvmType>)],
+    args: &[(Box<str>, VariableKind, AmvmType)],
     ret: &AmvmType,
-    body: &Vec<Command>,
+    body: &[Command],
 ) -> AmvmResult {
-    let value = ValueFun::Const(args.clone(), ret.clone(), body.clone());
-    let value = Value::Fun(value);
+    let value = if is_mutable {
+        create_mutable(scope, generics, args, ret, body)
+    } else {
+        create(scope, generics, args, ret, body)
+    };
 
     let name = name.to_string();
+    let value = value.stored_in(&scope.context);
     scope
         .context
         .lock()
@@ -23,3 +32,40 @@ pub fn eval(
 
     Ok(Value::Null)
 }
+
+/// Function value that captures the context of `scope`.
+pub fn create(
+    scope: &AmvmScope,
+    generics: &[(Box<str>, Option<AmvmType>)],
+    args: &[(Box<str>, VariableKind, AmvmType)],
+    ret: &AmvmType,
+    body: &[Command],
+) -> Value {
+    Value::Fun(ValueFun::Const(
+        generics.to_vec(),
+        args.to_vec(),
+        ret.clone(),
+        Rc::new(body.to_vec()),
+        FunEnv::new(&scope.context),
+    ))
+}
+
+/// Function value with its own context for static variables, which
+/// lives as long as the function. It's only used to look them up, its
+/// variables come from the context of `scope`.
+pub fn create_mutable(
+    scope: &AmvmScope,
+    generics: &[(Box<str>, Option<AmvmType>)],
+    args: &[(Box<str>, VariableKind, AmvmType)],
+    ret: &AmvmType,
+    body: &[Command],
+) -> Value {
+    Value::Fun(ValueFun::Mutable(
+        generics.to_vec(),
+        args.to_vec(),
+        ret.clone(),
+        Rc::new(body.to_vec()),
+        FunEnv::new(&scope.context),
+        Arc::new(Mutex::new(Context::new())),
+    ))
+}
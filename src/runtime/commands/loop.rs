@@ -1,20 +1,41 @@
+use std::rc::Rc;
+
 use crate::{
-    runtime::AmvmResult,
+    runtime::{limits, scope, AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, Command, Value},
 };
 
-pub fn eval(scope: &mut AmvmScope, body: &Vec<Command>) -> AmvmResult {
-    'l: loop {
-        let mut scope = scope.create_sub(body.clone());
+/// How a loop goes on after a run of its body.
+pub enum Flow {
+    Next,
+    /// The loop ends with this result
+    Exit(AmvmResult),
+}
 
-        for cmd in scope.body.clone().iter() {
-            match super::eval(&mut scope, cmd) {
-                Err(crate::runtime::AmvmPropagate::Break) => break 'l,
-                Err(e) => return Err(e),
-                _ => {}
-            };
-        }
+/// `@break` and `@continue` without a label or with `label` are for this
+/// loop, other errors leave it.
+pub fn flow(label: &Option<Box<str>>, result: AmvmResult) -> Flow {
+    match result {
+        Err(e) if e.breaks(label) => Flow::Exit(Ok(Value::Null)),
+        Err(e) if !e.continues(label) => Flow::Exit(Err(e)),
+        _ => Flow::Next,
     }
+}
 
-    Ok(Value::Null)
+/// Scope for a run of the body.
+pub fn run(scope: &AmvmScope, body: &Rc<Vec<Command>>) -> Result<AmvmScope, AmvmPropagate> {
+    // Empty loops still use fuel
+    limits::charge(scope)?;
+    Ok(scope.create_sub_shared(body))
+}
+
+pub fn eval(scope: &mut AmvmScope, label: &Option<Box<str>>, body: &[Command]) -> AmvmResult {
+    let body = Rc::new(body.to_vec());
+
+    loop {
+        let mut inner = run(scope, &body)?;
+        if let Flow::Exit(result) = flow(label, scope::eval(&mut inner, &body, true)) {
+            return result;
+        }
+    }
 }
@@ -0,0 +1,95 @@
+use crate::{
+    runtime::{expr, scope, types, AmvmPropagate, AmvmResult, AmvmVariable},
+    tokens::{
+        AmvmScope, Command, CommandExpression, CommandPattern, Value, ValueObject, VariableKind,
+    },
+};
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    value: &CommandExpression,
+    arms: &[(CommandPattern, Vec<Command>)],
+) -> AmvmResult {
+    let Some(mut scope) = select(scope, value, arms)? else {
+        return Ok(Value::Null);
+    };
+
+    let body = scope.body.clone();
+    scope::eval(&mut scope, &body, true)
+}
+
+/// Scope where the body of the first arm that matches runs, with the
+/// variables bound by its pattern.
+pub fn select(
+    scope: &mut AmvmScope,
+    value: &CommandExpression,
+    arms: &[(CommandPattern, Vec<Command>)],
+) -> Result<Option<AmvmScope>, AmvmPropagate> {
+    let value = expr::eval(scope, value)?.as_value();
+    let value = match value.as_ref() {
+        Value::Ref(var) => var.read(),
+        _ => value,
+    };
+
+    for (pattern, body) in arms {
+        let Some(bindings) = matches(scope, &value, pattern) else {
+            continue;
+        };
+
+        let scope = scope.create_sub(body.clone());
+        {
+            let mut context = scope.context.lock().unwrap();
+            for (name, value) in bindings {
+                context
+                    .variables
+                    .insert(name, AmvmVariable::new(VariableKind::Const, value));
+            }
+        }
+
+        return Ok(Some(scope));
+    }
+
+    Ok(None)
+}
+
+/// Variables bound by the pattern if the value matches it.
+fn matches(
+    scope: &AmvmScope,
+    value: &Value,
+    pattern: &CommandPattern,
+) -> Option<Vec<(String, Value)>> {
+    match pattern {
+        CommandPattern::Wildcard => Some(vec![]),
+        CommandPattern::Value(literal) => match (value, literal) {
+            (Value::Null, Value::Null) => Some(vec![]),
+            (Value::Bool(a), Value::Bool(b)) if a == b => Some(vec![]),
+            (Value::Char(a), Value::Char(b)) if a == b => Some(vec![]),
+            (Value::I16(a), Value::I16(b)) if a == b => Some(vec![]),
+            (Value::F32(a), Value::F32(b)) if a == b => Some(vec![]),
+            (Value::String(a), Value::String(b)) if a == b => Some(vec![]),
+            (Value::U8(a), Value::U8(b)) if a == b => Some(vec![]),
+            (Value::Usize(a), Value::Usize(b)) if a == b => Some(vec![]),
+            _ => None,
+        },
+        CommandPattern::Type(ty) => {
+            types::check(scope, value, ty, &mut types::AmvmGenerics::new()).then(Vec::new)
+        }
+        CommandPattern::Struct(ty, fields) => {
+            if !types::check(scope, value, ty, &mut types::AmvmGenerics::new()) {
+                return None;
+            }
+
+            let Value::Object(ValueObject::Instance(_, map)) = value else {
+                return None;
+            };
+
+            fields
+                .iter()
+                .map(|(field, var)| {
+                    let value = map.get(field.as_ref())?.read().unwrap().clone();
+                    Some((var.to_string(), value))
+                })
+                .collect()
+        }
+    }
+}
@@ -13,26 +13,62 @@ pub fn eval(scope: &mut AmvmScope, value: &CommandExpression) -> AmvmResult {
 
 fn print_value(value: &Value) {
     match value {
-        Value::Null => print!("undefined"),
+        Value::Null => print!("null"),
         Value::Bool(v) => print!("{v}"),
         Value::Char(v) => print!("{v}"),
 
         Value::U8(v) => print!("{v}"),
+        Value::Usize(v) => print!("{v}"),
         Value::I16(v) => print!("{v}"),
         Value::F32(v) => print!("{v}"),
 
         Value::Ref(v) => print_value(&*v.read()),
         Value::String(v) => print!("{v}"),
+        Value::List(values) => {
+            print!("[");
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    print!(", ");
+                }
+                print_value(value);
+            }
+            print!("]");
+        }
+        Value::Map(map) => {
+            print!("{{");
+            for (i, (key, value)) in map.iter().enumerate() {
+                if i != 0 {
+                    print!(", ");
+                }
+                print_value(&key.to_value());
+                print!(" => ");
+                print_value(value);
+            }
+            print!("}}");
+        }
+        Value::Tuple(values) => {
+            print!("(");
+            for (i, value) in values.iter().enumerate() {
+                if i != 0 {
+                    print!(", ");
+                }
+                print_value(value);
+            }
+            print!(")");
+        }
 
         Value::Object(value) => match value {
             ValueObject::Native(ptr) => print!("[Native 0x{:02x}]", *ptr as u32),
             ValueObject::PropertyMap(map) => print!("# {map:#?}"),
             ValueObject::Instance(ty, map) => print!("{} {map:#?}", ty.flat_name()),
+            ValueObject::Variant(ty, variant, map) => {
+                print!("{}.{variant} {map:#?}", ty.flat_name())
+            }
         },
         Value::Fun(v) => match v {
             ValueFun::Native(ref args, ret, _)
-            | ValueFun::Const(ref args, ret, _)
-            | ValueFun::Mutable(ref args, ret, _) => {
+            | ValueFun::Const(_, ref args, ret, ..)
+            | ValueFun::Mutable(_, ref args, ret, ..) => {
                 print!(
                     "[Function ({args}) {ret}]",
                     args = args
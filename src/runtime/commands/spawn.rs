@@ -0,0 +1,29 @@
+use crate::{
+    runtime::{expr, tasks, AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, CommandExpression, Value},
+};
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    name: &CommandExpression,
+    args: &[CommandExpression],
+) -> AmvmResult {
+    let callee = super::call::callee_name(name);
+    let name = expr::eval(scope, name)?;
+    let name = name.as_value();
+    let Some(fun) = name.as_function() else {
+        return Err(AmvmPropagate::Err(scope.error("Spawning a non-function")));
+    };
+
+    let mut args_evaluated = Vec::with_capacity(args.len());
+
+    for arg in args {
+        args_evaluated.push(expr::eval(scope, arg)?.as_ref());
+    }
+
+    let task = tasks::spawn(scope, callee, fun.clone(), args_evaluated);
+
+    scope.context.lock().unwrap().push_prev_value(task);
+
+    Ok(Value::Null)
+}
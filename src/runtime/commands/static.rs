@@ -0,0 +1,26 @@
+use crate::{
+    runtime::{expr, AmvmPropagate, AmvmResult, AmvmVariable, Context},
+    tokens::{AmvmScope, CommandExpression, Value, VariableKind},
+};
+
+/// Declare a variable in the state of the mutable function being called,
+/// it's only initialized the first time.
+pub fn eval(scope: &mut AmvmScope, name: &str, value: &CommandExpression) -> AmvmResult {
+    let Some(state) = Context::function_state(&scope.context) else {
+        return Err(AmvmPropagate::Err(scope.error(
+            "Static variables only can be declared in mutable functions",
+        )));
+    };
+
+    if state.lock().unwrap().variables.contains_key(name) {
+        return Ok(Value::Null);
+    }
+
+    let value = expr::eval(scope, value)?.as_value();
+    state.lock().unwrap().variables.insert(
+        name.to_string(),
+        AmvmVariable::new(VariableKind::Var, value.as_ref().clone()),
+    );
+
+    Ok(Value::Null)
+}
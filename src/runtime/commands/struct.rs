@@ -1,14 +1,50 @@
 use crate::{
-    runtime::AmvmResult,
+    runtime::{types, AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, AmvmType, AmvmTypeDefinition, Value},
 };
 
-pub fn eval(scope: &mut AmvmScope, name: &str, body: &Vec<(Box<str>, AmvmType)>) -> AmvmResult {
-    let declaration = AmvmTypeDefinition::Struct {
-        generics: vec![],
-        fields: body.clone(),
+pub fn eval(
+    scope: &mut AmvmScope,
+    name: &str,
+    generics: &[(Box<str>, Option<AmvmType>)],
+    inherits: &[AmvmType],
+    body: &[(Box<str>, AmvmType)],
+) -> AmvmResult {
+    let mut declaration = AmvmTypeDefinition::Struct {
+        generics: generics.to_vec(),
+        fields: body.to_vec(),
     };
 
+    if !inherits.is_empty() {
+        for parent in inherits {
+            let is_declared = parent
+                .name()
+                .and_then(|name| scope.context.lock().unwrap().get_struct(name))
+                .is_some();
+
+            if !is_declared {
+                return Err(AmvmPropagate::Err(
+                    scope.error("Inherited type is not declared"),
+                ));
+            }
+        }
+
+        for (field, inherited) in types::inherited_fields(scope, inherits) {
+            let own = body.iter().find(|f| f.0 == field);
+
+            let inherited = types::resolve(scope, &inherited);
+            let own = own.map(|own| types::resolve(scope, &own.1));
+
+            if own.is_some_and(|own| !types::conforms(&own, &inherited)) {
+                return Err(AmvmPropagate::Err(
+                    scope.error("Field type doesn't conform to the inherited type"),
+                ));
+            }
+        }
+
+        declaration = AmvmTypeDefinition::Inheritance(inherits.to_vec(), Box::new(declaration));
+    }
+
     scope
         .context
         .lock()
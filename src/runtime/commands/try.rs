@@ -0,0 +1,55 @@
+use crate::{
+    runtime::{scope, AmvmResult, AmvmVariable},
+    tokens::{AmvmScope, Command, Value, VariableKind},
+};
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    body: &[Command],
+    catch: &Option<(Box<str>, Vec<Command>)>,
+    finally: &Option<Vec<Command>>,
+) -> AmvmResult {
+    // Tail calls are done here, so their errors can still be caught
+    let result = scope::eval(scope, body, false);
+    let mut result = super::call::finish_tail_call(scope, result);
+
+    if let Some(mut inner) = catch_scope(scope, &result, catch) {
+        let body = inner.body.clone();
+        let caught = scope::eval(&mut inner, &body, true);
+        result = super::call::finish_tail_call(&mut inner, caught);
+    }
+
+    let Some(finally) = finally else {
+        return result.map(|_| Value::Null);
+    };
+
+    let finally = scope::eval(scope, finally, false);
+    finish(result, super::call::finish_tail_call(scope, finally))
+}
+
+/// Scope where the `@catch` body runs, if it catches the result of the
+/// `@try` body.
+pub fn catch_scope(
+    scope: &AmvmScope,
+    result: &AmvmResult,
+    catch: &Option<(Box<str>, Vec<Command>)>,
+) -> Option<AmvmScope> {
+    let (Err(propagate), Some((name, body))) = (result, catch) else {
+        return None;
+    };
+    let error = propagate.to_catchable()?;
+
+    let scope = scope.create_sub(body.clone());
+    scope.context.lock().unwrap().variables.insert(
+        name.to_string(),
+        AmvmVariable::new(VariableKind::Const, error),
+    );
+
+    Some(scope)
+}
+
+/// Result of the `@try` once `@finally` ran, its errors replace the
+/// previous result.
+pub fn finish(result: AmvmResult, finally: AmvmResult) -> AmvmResult {
+    finally.and(result).map(|_| Value::Null)
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+
+use crate::{
+    runtime::{AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, AmvmType, Value},
+};
+
+pub fn eval(scope: &mut AmvmScope, name: &str, ty: &AmvmType) -> AmvmResult {
+    if refers_to(scope, ty, name, &mut HashSet::new()) {
+        return Err(AmvmPropagate::Err(scope.error("Recursive type alias")));
+    }
+
+    scope
+        .context
+        .lock()
+        .unwrap()
+        .aliases
+        .insert(name.to_string(), ty.clone());
+
+    Ok(Value::Null)
+}
+
+/// Whether checking a value against `ty` would get back to the alias `name`
+/// without looking inside the value. Lists, tuples and the like can refer
+/// to it, their items are checked against it.
+fn refers_to(
+    scope: &AmvmScope,
+    ty: &AmvmType,
+    name: &str,
+    visited: &mut HashSet<Box<str>>,
+) -> bool {
+    match ty {
+        AmvmType::Named(other) if other.as_ref() == name => true,
+        AmvmType::Named(other) => {
+            if !visited.insert(other.clone()) {
+                return false;
+            }
+
+            let alias = scope.context.lock().unwrap().get_alias(other);
+            alias.is_some_and(|alias| refers_to(scope, &alias, name, visited))
+        }
+        AmvmType::Union(a, b) => {
+            refers_to(scope, a, name, visited) || refers_to(scope, b, name, visited)
+        }
+        AmvmType::Nullable(ty) => refers_to(scope, ty, name, visited),
+        _ => false,
+    }
+}
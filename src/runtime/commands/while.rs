@@ -0,0 +1,67 @@
+use std::rc::Rc;
+
+use super::r#loop::{self, Flow};
+use crate::{
+    runtime::{expr, scope, AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, Command, CommandExpression, Value},
+};
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    label: &Option<Box<str>>,
+    condition: &CommandExpression,
+    body: &[Command],
+    is_do: bool,
+) -> AmvmResult {
+    let body = Rc::new(body.to_vec());
+
+    while let Some(mut inner) = run(scope, &body, condition, is_do)? {
+        if let Flow::Exit(result) = r#loop::flow(label, scope::eval(&mut inner, &body, true)) {
+            return result;
+        }
+
+        if !repeats(&mut inner, condition, is_do)? {
+            break;
+        }
+    }
+
+    Ok(Value::Null)
+}
+
+/// Scope for a run of the body, `None` when the condition doesn't hold.
+/// The condition of `@do` is checked after each run instead, by [repeats].
+pub fn run(
+    scope: &AmvmScope,
+    body: &Rc<Vec<Command>>,
+    condition: &CommandExpression,
+    is_do: bool,
+) -> Result<Option<AmvmScope>, AmvmPropagate> {
+    let mut inner = scope.create_sub_shared(body);
+    if !is_do && !check(&mut inner, condition)? {
+        return Ok(None);
+    }
+
+    Ok(Some(inner))
+}
+
+/// Whether the body runs again after the run in `inner`. The condition
+/// of `@do` can use the variables of the body.
+pub fn repeats(
+    inner: &mut AmvmScope,
+    condition: &CommandExpression,
+    is_do: bool,
+) -> Result<bool, AmvmPropagate> {
+    Ok(!is_do || check(inner, condition)?)
+}
+
+/// Value of the condition, which should be boolean.
+pub fn check(scope: &mut AmvmScope, condition: &CommandExpression) -> Result<bool, AmvmPropagate> {
+    let condition = expr::eval(scope, condition)?.as_value();
+    let Value::Bool(condition) = condition.as_ref() else {
+        return Err(AmvmPropagate::Err(
+            scope.error("Condition should be boolean"),
+        ));
+    };
+
+    Ok(*condition)
+}
@@ -0,0 +1,70 @@
+use crate::runtime::machine::{Machine, State, Suspend};
+use crate::runtime::{expr, types, AmvmPropagate};
+use crate::{
+    runtime::AmvmResult,
+    tokens::{AmvmScope, AmvmType, Command, CommandExpression, Value},
+};
+
+/// Functions with a `@yield` in their body, outside of nested functions,
+/// are generators.
+pub fn is_generator(body: &[Command]) -> bool {
+    body.iter().any(|cmd| match cmd {
+        Command::Yield { .. } => true,
+        Command::Conditional {
+            body, otherwise, ..
+        } => is_generator(body) || otherwise.as_deref().is_some_and(is_generator),
+        Command::For { body, .. }
+        | Command::Loop { body, .. }
+        | Command::Scope { body }
+        | Command::While { body, .. } => is_generator(body),
+        Command::Match { arms, .. } => arms.iter().any(|(_, body)| is_generator(body)),
+        Command::Try {
+            body,
+            catch,
+            finally,
+        } => {
+            is_generator(body)
+                || catch.as_ref().is_some_and(|(_, body)| is_generator(body))
+                || finally.as_deref().is_some_and(is_generator)
+        }
+        _ => false,
+    })
+}
+
+/// Iterator that runs the body of a generator, from the scope of a call to
+/// it where the arguments are already declared. The body only runs when
+/// asking for the next value, until the following `@yield`.
+pub fn generator(inner: AmvmScope, item: &AmvmType, generics: types::AmvmGenerics) -> Value {
+    let mut machine = Machine::generator(inner, item.clone(), generics);
+
+    expr::iter::from_fn(move |_| match machine.resume() {
+        State::Suspended(Suspend::Yield(value)) => Ok(Some(value)),
+        State::Suspended(_) => unreachable!("Generators only yield"),
+        State::Finished(result) => result.map(|_| None),
+    })
+}
+
+/// Value given by a `@yield` in a generator that yields `item` values.
+pub fn value(
+    scope: &mut AmvmScope,
+    value: &CommandExpression,
+    item: &AmvmType,
+    generics: &types::AmvmGenerics,
+) -> AmvmResult {
+    let value = expr::eval(scope, value)?.as_value().as_ref().clone();
+
+    if !types::check(scope, &value, item, &mut generics.clone()) {
+        return Err(AmvmPropagate::Err(
+            scope.error("Yielded value doesn't match its declared type"),
+        ));
+    }
+
+    Ok(value)
+}
+
+/// Generators run their body themselves, any other `@yield` is misplaced.
+pub fn eval(scope: &mut AmvmScope, _value: &CommandExpression) -> AmvmResult {
+    Err(AmvmPropagate::Err(
+        scope.error("Yield only can be used inside functions"),
+    ))
+}
@@ -0,0 +1,16 @@
+use crate::tokens::{AmvmType, AmvmTypeDefinition};
+
+/// Runtime errors caught by `@catch`
+pub fn amvm_error_type() -> AmvmTypeDefinition {
+    let nullable = |name: &str| AmvmType::Nullable(Box::new(AmvmType::Named(Box::from(name))));
+
+    AmvmTypeDefinition::Struct {
+        generics: vec![],
+        fields: vec![
+            ("message".into(), AmvmType::Named("string".into())),
+            ("file".into(), nullable("string")),
+            ("line".into(), nullable("usize")),
+            ("column".into(), nullable("usize")),
+        ],
+    }
+}
@@ -1,2 +1,9 @@
+mod error;
 mod iterator;
+mod range;
+mod task;
+
+pub use error::*;
 pub use iterator::*;
+pub use range::*;
+pub use task::*;
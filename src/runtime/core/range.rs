@@ -0,0 +1,17 @@
+use crate::tokens::{AmvmType, AmvmTypeDefinition};
+
+/// Ranges created with `..` and `..=`, they can be iterated many times
+pub fn amvm_range_type() -> AmvmTypeDefinition {
+    #[allow(non_snake_case)]
+    let T = || AmvmType::Named(Box::from("T"));
+
+    AmvmTypeDefinition::Struct {
+        generics: vec![("T".into(), None)],
+        fields: vec![
+            ("start".into(), T()),
+            ("end".into(), T()),
+            ("step".into(), AmvmType::Nullable(Box::new(T()))),
+            ("inclusive".into(), AmvmType::Named("bool".into())),
+        ],
+    }
+}
@@ -0,0 +1,9 @@
+use crate::tokens::{AmvmType, AmvmTypeDefinition};
+
+/// Tasks started with `@spawn`
+pub fn amvm_task_type() -> AmvmTypeDefinition {
+    AmvmTypeDefinition::Struct {
+        generics: vec![],
+        fields: vec![("id".into(), AmvmType::Named("usize".into()))],
+    }
+}
@@ -1,47 +1,157 @@
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::{Arc, RwLock};
 use std::{error, fmt};
 
-use crate::tokens::AmvmMeta;
+use crate::tokens::{AmvmFrame, AmvmMeta, AmvmType, Value, ValueObject};
 
 #[derive(Debug, Clone)]
 pub enum AmvmError {
-    Other(Vec<Rc<AmvmMeta>>, &'static str),
+    /// Message with the code positions where it happened, from the
+    /// innermost scope, and the call being run
+    Other(Vec<Rc<AmvmMeta>>, Option<Rc<AmvmFrame>>, &'static str),
 }
 
-impl fmt::Display for AmvmError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl AmvmError {
+    pub fn message(&self) -> &'static str {
         match self {
-            Self::Other(meta, ctx) => {
-                writeln!(f, "\x1b[1;31merror:\x1b[0;1m {ctx}\x1b[0m")?;
+            Self::Other(_, _, ctx) => ctx,
+        }
+    }
+
+    /// Instance of `#Error` with the message and where it happened.
+    pub fn to_value(&self) -> Value {
+        let Self::Other(meta, _, ctx) = self;
+        let meta = meta.first();
 
-                let mut has_alternatives = false;
+        let file = meta
+            .and_then(|meta| meta.file_name.0.as_ref())
+            .map_or(Value::Null, |file| Value::String(file.to_string()));
+        let line = meta.map_or(Value::Null, |meta| Value::Usize(meta.pos.0 as usize));
+        let column = meta.map_or(Value::Null, |meta| Value::Usize(meta.pos.1 as usize));
+
+        let mut fields = HashMap::new();
+        for (name, value) in [
+            ("message", Value::String(ctx.to_string())),
+            ("file", file),
+            ("line", line),
+            ("column", column),
+        ] {
+            fields.insert(name.to_owned(), Arc::new(RwLock::new(value)));
+        }
+
+        Value::Object(ValueObject::Instance(
+            AmvmType::Named(Box::from("Error")),
+            fields,
+        ))
+    }
+}
+
+fn is_same_place(a: &AmvmMeta, b: &AmvmMeta) -> bool {
+    a.file_name == b.file_name && a.pos == b.pos && a.code == b.code
+}
+
+/// Where the code of `meta` is, like the snippets of the error
+fn location(meta: &AmvmMeta, debug_ir: bool) -> String {
+    match &meta.alternative {
+        Some(alternative) if !debug_ir => alternative.location(false),
+        _ => meta.location(true),
+    }
+}
+
+/// Write the active calls from the innermost one, with the position being
+/// run in each of them.
+fn write_stack_trace(
+    f: &mut fmt::Formatter<'_>,
+    meta: Option<&Rc<AmvmMeta>>,
+    frame: &Rc<AmvmFrame>,
+    debug_ir: bool,
+) -> fmt::Result {
+    let at = |meta: Option<&Rc<AmvmMeta>>| {
+        meta.map(|meta| format!(" \x1b[2m({})\x1b[0m", location(meta, debug_ir)))
+            .unwrap_or_default()
+    };
+
+    writeln!(f, "\x1b[1mstack trace:\x1b[0m")?;
+
+    // Each call is at the call site of the one it's running
+    let mut position = meta;
+    let calls = frame.iter().map(|frame| {
+        let call = (frame, position);
+        position = frame.call_site.as_ref();
+        call
+    });
+
+    let mut calls = calls.collect::<Vec<_>>().into_iter().peekable();
+    while let Some((frame, position)) = calls.next() {
+        writeln!(f, "  at {frame}{}", at(position))?;
+
+        // Deep recursion repeats the same call, only the first and last
+        // ones are shown as their arguments can differ
+        let mut repeated = 0;
+        let mut last = None;
+        while let Some(next) = calls.next_if(|(next, next_position)| {
+            next.name == frame.name
+                && match (position, next_position) {
+                    (Some(a), Some(b)) => is_same_place(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        }) {
+            repeated += 1;
+            last = Some(next);
+        }
+        if let Some((last, last_position)) = last {
+            if repeated > 1 {
+                writeln!(f, "  ... repeated {} more times", repeated - 1)?;
+            }
+            writeln!(f, "  at {last}{}", at(last_position))?;
+        }
+    }
+
+    writeln!(f, "  at <main>{}", at(position))
+}
+
+impl AmvmError {
+    /// The error shown with another message, like the value of an uncaught
+    /// `@throw`.
+    pub fn report(&self, message: &str) -> String {
+        Report(self, message).to_string()
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, message: &str) -> fmt::Result {
+        match self {
+            Self::Other(meta, frame, _) => {
+                writeln!(f, "\x1b[1;31merror:\x1b[0;1m {message}\x1b[0m")?;
 
                 let debug_ir = std::env::var("AMVM_IR_DEBUG")
                     .map(|x| x != "0" && x != "false")
                     .unwrap_or(false);
 
-                for meta in meta.iter() {
+                // The calls are in the stack trace, only where it happened
+                // is shown with its code
+                if let Some(meta) = meta.first() {
                     if let Some(alternative) = meta.alternative.as_ref() {
-                        has_alternatives = true;
                         write!(f, "{}", alternative.display(false, false))?;
 
                         if debug_ir {
                             write!(f, "{}", meta.display(true, true))?;
+                        } else {
+                            writeln!(
+                                f,
+                                "\x1b[31mAlternative code detected, use AMVM_IR_DEBUG=1 to see original.\x1b[0m"
+                            )?;
                         }
                     } else {
                         write!(f, "{}", meta.display(true, false))?;
                     }
                 }
 
-                if !debug_ir && has_alternatives {
-                    writeln!(
-                        f,
-                        "\x1b[31mAlternative code detected, use AMVM_IR_DEBUG=1 to see original.\x1b[0m"
-                    )?;
+                if let Some(frame) = frame {
+                    write_stack_trace(f, meta.first(), frame, debug_ir)?;
                 }
 
                 if meta.is_empty() {
-                    writeln!(f, "\x1b[31mNo backtrace. Available through AML3_DEBUG=1 when compiling from aml3\x1b[0m")?;
+                    writeln!(f, "\x1b[31mNo code positions. Available through AML3_DEBUG=1 when compiling from aml3\x1b[0m")?;
                 }
 
                 Ok(())
@@ -50,4 +160,19 @@ impl fmt::Display for AmvmError {
     }
 }
 
+/// Error written with another message
+struct Report<'a>(&'a AmvmError, &'a str);
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, self.1)
+    }
+}
+
+impl fmt::Display for AmvmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, self.message())
+    }
+}
+
 impl error::Error for AmvmError {}
@@ -25,6 +25,11 @@ pub fn eval_strict(scope: &mut AmvmScope, a: &Value, b: &Value) -> AmvmResult {
                 scope.error("Attempt to add with overflow")
             })?))
         }
+        (Value::Usize(a), Value::Usize(b)) => {
+            Ok(Value::Usize(a.checked_add(*b).ok_or_else(|| {
+                scope.error("Attempt to add with overflow")
+            })?))
+        }
         (Value::I16(a), Value::I16(b)) => {
             Ok(Value::I16(a.checked_add(*b).ok_or_else(|| {
                 scope.error("Attempt to add with overflow")
@@ -41,6 +46,7 @@ fn eval_cast_strictless_string(scope: &mut AmvmScope, a: &Value, b: &Value) -> A
     match (a, b) {
         (Value::Null, Value::Null) => Ok(Value::Null),
         (Value::U8(_), Value::U8(_)) => eval_strict(scope, a, b),
+        (Value::Usize(_), Value::Usize(_)) => eval_strict(scope, a, b),
         (Value::I16(_), Value::I16(_)) => eval_strict(scope, a, b),
         (Value::F32(_), Value::F32(_)) => eval_strict(scope, a, b),
         (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{a}{b}"))),
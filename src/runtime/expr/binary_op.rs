@@ -1,13 +1,21 @@
 use crate::{
-    runtime::AmvmResult,
+    runtime::{AmvmPropagate, AmvmResult},
     tokens::{AmvmScope, CommandExpression, Value},
 };
 
-#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
 pub enum BinaryOpKind {
     Sub,
     Mult,
     Div,
+    Mod,
+    Pow,
+
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 pub fn eval(
@@ -24,22 +32,59 @@ pub fn eval(
     eval_post(scope, kind, a, b)
 }
 
-pub fn eval_post(_: &mut AmvmScope, kind: BinaryOpKind, a: &Value, b: &Value) -> AmvmResult {
-    macro_rules! impl_ops {
-        ($a:ident, $b:ident) => {
+pub fn eval_post(scope: &mut AmvmScope, kind: BinaryOpKind, a: &Value, b: &Value) -> AmvmResult {
+    // Integers fail instead of overflowing
+    macro_rules! impl_int_ops {
+        ($a:ident, $b:ident) => {{
+            let ($a, $b) = (*$a, *$b);
             match kind {
-                BinaryOpKind::Sub => $a - $b,
-                BinaryOpKind::Mult => $a * $b,
-                BinaryOpKind::Div => $a / $b,
+                BinaryOpKind::Sub => $a
+                    .checked_sub($b)
+                    .ok_or("Attempt to subtract with overflow"),
+                BinaryOpKind::Mult => $a
+                    .checked_mul($b)
+                    .ok_or("Attempt to multiply with overflow"),
+                BinaryOpKind::Div if $b == 0 => Err("Attempt to divide by zero"),
+                BinaryOpKind::Div => $a.checked_div($b).ok_or("Attempt to divide with overflow"),
+                BinaryOpKind::Mod if $b == 0 => Err("Attempt to calculate the remainder by zero"),
+                BinaryOpKind::Mod => $a
+                    .checked_rem($b)
+                    .ok_or("Attempt to calculate the remainder with overflow"),
+                BinaryOpKind::Pow => u32::try_from($b)
+                    .ok()
+                    .and_then(|b| $a.checked_pow(b))
+                    .ok_or("Attempt to calculate the power with overflow"),
+
+                BinaryOpKind::BitAnd => Ok($a & $b),
+                BinaryOpKind::BitOr => Ok($a | $b),
+                BinaryOpKind::BitXor => Ok($a ^ $b),
+                BinaryOpKind::Shl => u32::try_from($b)
+                    .ok()
+                    .and_then(|b| $a.checked_shl(b))
+                    .ok_or("Attempt to shift left with overflow"),
+                BinaryOpKind::Shr => u32::try_from($b)
+                    .ok()
+                    .and_then(|b| $a.checked_shr(b))
+                    .ok_or("Attempt to shift right with overflow"),
             }
-        };
+        }};
     }
 
-    match (a, b) {
-        (Value::U8(a), Value::U8(b)) => Ok(Value::U8(impl_ops!(a, b))),
-        (Value::I16(a), Value::I16(b)) => Ok(Value::I16(impl_ops!(a, b))),
-        (Value::F32(a), Value::F32(b)) => Ok(Value::F32(impl_ops!(a, b))),
+    let result = match (a, b) {
+        (Value::U8(a), Value::U8(b)) => impl_int_ops!(a, b).map(Value::U8),
+        (Value::I16(a), Value::I16(b)) => impl_int_ops!(a, b).map(Value::I16),
+        (Value::Usize(a), Value::Usize(b)) => impl_int_ops!(a, b).map(Value::Usize),
+        (Value::F32(a), Value::F32(b)) => match kind {
+            BinaryOpKind::Sub => Ok(Value::F32(a - b)),
+            BinaryOpKind::Mult => Ok(Value::F32(a * b)),
+            BinaryOpKind::Div => Ok(Value::F32(a / b)),
+            BinaryOpKind::Mod => Ok(Value::F32(a % b)),
+            BinaryOpKind::Pow => Ok(Value::F32(a.powf(*b))),
+            _ => Err("Bitwise operations are only available for integers"),
+        },
 
-        _ => panic!("Invalid binary operation, should be the same type"),
-    }
+        _ => Err("Invalid binary operation, both sides should be the same number type"),
+    };
+
+    result.map_err(|err| AmvmPropagate::Err(scope.error(err)))
 }
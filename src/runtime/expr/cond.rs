@@ -1,6 +1,13 @@
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+
 use crate::{
-    runtime::{expr, AmvmResult},
-    tokens::{AmvmScope, BinaryKind, CommandExpression, Value},
+    runtime::{expr, AmvmPropagate, AmvmResult},
+    tokens::{
+        AmvmScope, AmvmTypeCasting, BinaryKind, CommandExpression, Value, ValueFun, ValueMap,
+        ValueObject,
+    },
 };
 
 pub fn eval(
@@ -14,36 +21,252 @@ pub fn eval(
     let binding = expr::eval(scope, b)?.as_value();
     let b = binding.as_ref();
 
-    match kind {
-        BinaryKind::GreaterThanEqual => match (a, b) {
-            (Value::U8(a), Value::U8(b)) => Ok(Value::Bool(a >= b)),
-            (Value::I16(a), Value::I16(b)) => Ok(Value::Bool(a >= b)),
-            (Value::F32(a), Value::F32(b)) => Ok(Value::Bool(a >= b)),
-            (a, b) => todo!("{a:?} {b:?}"),
-        },
-        BinaryKind::LessThan => match (a, b) {
-            (Value::U8(a), Value::U8(b)) => Ok(Value::Bool(a < b)),
-            (Value::I16(a), Value::I16(b)) => Ok(Value::Bool(a < b)),
-            (Value::F32(a), Value::F32(b)) => Ok(Value::Bool(a < b)),
-            (a, b) => todo!("{a:?} {b:?}"),
-        },
-        BinaryKind::LessThanEqual => match (a, b) {
-            (Value::U8(a), Value::U8(b)) => Ok(Value::Bool(a <= b)),
-            (Value::I16(a), Value::I16(b)) => Ok(Value::Bool(a <= b)),
-            (Value::F32(a), Value::F32(b)) => Ok(Value::Bool(a <= b)),
-            (a, b) => todo!("{a:?} {b:?}"),
-        },
-        BinaryKind::NotEqual => match (a, b) {
-            (Value::String(a), Value::String(b)) => Ok(Value::Bool(a != b)),
-            (Value::String(_), _) => Ok(Value::Bool(false)),
-            (Value::Null, Value::Null) => Ok(Value::Bool(true)),
-            (a, b) => todo!("{a:?} {b:?}"),
-        },
-        BinaryKind::Equal => match (a, b) {
-            (Value::String(a), Value::String(b)) => Ok(Value::Bool(a == b)),
-            (Value::Bool(a), Value::Bool(b)) => Ok(Value::Bool(a == b)),
-            (a, b) => todo!("{a:?} {b:?}"),
+    let result = match kind {
+        BinaryKind::Equal => loose_equals(scope, a, b),
+        BinaryKind::NotEqual => loose_equals(scope, a, b).map(|eq| !eq),
+        BinaryKind::GreaterThan => loose_order(scope, a, b).map(|o| o.is_some_and(Ordering::is_gt)),
+        BinaryKind::GreaterThanEqual => {
+            loose_order(scope, a, b).map(|o| o.is_some_and(Ordering::is_ge))
+        }
+        BinaryKind::LessThan => loose_order(scope, a, b).map(|o| o.is_some_and(Ordering::is_lt)),
+        BinaryKind::LessThanEqual => {
+            loose_order(scope, a, b).map(|o| o.is_some_and(Ordering::is_le))
+        }
+        _ => Err("Expected a comparison"),
+    };
+
+    result
+        .map(Value::Bool)
+        .map_err(|err| AmvmPropagate::Err(scope.error(err)))
+}
+
+/// Number of any type, to compare numbers of different types when the
+/// header allows casting.
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::U8(v) => Some(Self::Int(*v as i128)),
+            Value::I16(v) => Some(Self::Int(*v as i128)),
+            Value::Usize(v) => Some(Self::Int(*v as i128)),
+            Value::F32(v) => Some(Self::Float(*v as f64)),
+            _ => None,
+        }
+    }
+
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => Some(a.cmp(b)),
+            (Self::Int(a), Self::Float(b)) => (*a as f64).partial_cmp(b),
+            (Self::Float(a), Self::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Self::Float(a), Self::Float(b)) => a.partial_cmp(b),
+        }
+    }
+}
+
+fn deref(value: &Value) -> Value {
+    match value {
+        Value::Ref(var) => deref(&var.read()),
+        value => value.clone(),
+    }
+}
+
+fn is_same_type(a: &Value, b: &Value) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// Equality between values of any type, following the casting rules
+/// of the header.
+fn loose_equals(scope: &AmvmScope, a: &Value, b: &Value) -> Result<bool, &'static str> {
+    let (a, b) = (deref(a), deref(b));
+    if is_same_type(&a, &b) {
+        return Ok(equals(&a, &b));
+    }
+
+    if matches!(a, Value::Null) || matches!(b, Value::Null) {
+        return Ok(false);
+    }
+
+    match scope.header.sum_kind {
+        AmvmTypeCasting::Strict => Ok(false),
+        ref mode => {
+            if let (Some(a), Some(b)) = (Number::from_value(&a), Number::from_value(&b)) {
+                return Ok(a.partial_cmp(&b) == Some(Ordering::Equal));
+            }
+
+            match (mode, serialize(&a), serialize(&b)) {
+                (AmvmTypeCasting::TypeCastingStrict, ..) => {
+                    Err("Can't compare values of different types")
+                }
+                (_, Some(a), Some(b)) => Ok(a == b),
+                (AmvmTypeCasting::TypeCastingStrictlessString, ..) => Ok(false),
+                _ => Err("Can't compare values of different types"),
+            }
+        }
+    }
+}
+
+/// Ordering between values of any type, following the casting rules
+/// of the header. `None` if they are not comparable, like `NaN`.
+fn loose_order(scope: &AmvmScope, a: &Value, b: &Value) -> Result<Option<Ordering>, &'static str> {
+    let (a, b) = (deref(a), deref(b));
+    if is_same_type(&a, &b) {
+        return order(&a, &b);
+    }
+
+    if matches!(a, Value::Null) || matches!(b, Value::Null) {
+        return Err("Null can't be ordered");
+    }
+
+    match scope.header.sum_kind {
+        AmvmTypeCasting::Strict => Err("Can't compare values of different types"),
+        ref mode => {
+            if let (Some(a), Some(b)) = (Number::from_value(&a), Number::from_value(&b)) {
+                return Ok(a.partial_cmp(&b));
+            }
+
+            match (mode, serialize(&a), serialize(&b)) {
+                (AmvmTypeCasting::TypeCastingStrict, ..) => {
+                    Err("Can't compare values of different types")
+                }
+                (_, Some(a), Some(b)) => Ok(Some(a.cmp(&b))),
+                _ => Err("Can't compare values of different types"),
+            }
+        }
+    }
+}
+
+/// Ordering between values of the same type. Strings are compared
+/// lexicographically, and tuples and lists item by item.
+fn order(a: &Value, b: &Value) -> Result<Option<Ordering>, &'static str> {
+    match (a, b) {
+        (Value::Ref(a), b) => order(&a.read(), b),
+        (a, Value::Ref(b)) => order(a, &b.read()),
+
+        (Value::Bool(a), Value::Bool(b)) => Ok(Some(a.cmp(b))),
+        (Value::Char(a), Value::Char(b)) => Ok(Some(a.cmp(b))),
+        (Value::I16(a), Value::I16(b)) => Ok(Some(a.cmp(b))),
+        (Value::F32(a), Value::F32(b)) => Ok(a.partial_cmp(b)),
+        (Value::String(a), Value::String(b)) => Ok(Some(a.cmp(b))),
+        (Value::U8(a), Value::U8(b)) => Ok(Some(a.cmp(b))),
+        (Value::Usize(a), Value::Usize(b)) => Ok(Some(a.cmp(b))),
+        (Value::Tuple(a), Value::Tuple(b)) | (Value::List(a), Value::List(b)) => {
+            for (a, b) in a.iter().zip(b) {
+                if !is_same_type(&deref(a), &deref(b)) {
+                    return Err("Can't compare values of different types");
+                }
+
+                match order(a, b)? {
+                    Some(Ordering::Equal) => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+
+            Ok(Some(a.len().cmp(&b.len())))
+        }
+        _ => Err("Values of this type can't be ordered"),
+    }
+}
+
+/// Text representation used to compare values of different types, only
+/// available for values without identity.
+fn serialize(value: &Value) -> Option<String> {
+    let join = |values: &[Value]| {
+        values
+            .iter()
+            .map(serialize)
+            .collect::<Option<Vec<String>>>()
+            .map(|values| values.join(", "))
+    };
+
+    match value {
+        Value::Null
+        | Value::Bool(_)
+        | Value::Char(_)
+        | Value::I16(_)
+        | Value::F32(_)
+        | Value::String(_)
+        | Value::U8(_)
+        | Value::Usize(_) => Some(value.to_string_or_default()),
+        Value::Ref(var) => serialize(&var.read()),
+        Value::List(values) => Some(format!("[{}]", join(values)?)),
+        Value::Tuple(values) => Some(format!("({})", join(values)?)),
+        Value::Fun(_) | Value::Map(_) | Value::Object(_) => None,
+    }
+}
+
+/// Tuples and lists are equal when they have the same length and every
+/// element is equal to the one in the same position.
+fn items_equal(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equals(a, b))
+}
+
+/// Maps are equal when they have the same entries, in any order.
+fn maps_equal(a: &ValueMap, b: &ValueMap) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .all(|(key, value)| b.get(key).is_some_and(|other| equals(value, other)))
+}
+
+/// Objects are equal when they have the same fields with equal values.
+fn fields_equal(
+    a: &std::collections::HashMap<String, Arc<RwLock<Value>>>,
+    b: &std::collections::HashMap<String, Arc<RwLock<Value>>>,
+) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(name, value)| {
+            b.get(name).is_some_and(|other| {
+                Arc::ptr_eq(value, other) || equals(&value.read().unwrap(), &other.read().unwrap())
+            })
+        })
+}
+
+/// Functions are only equal to themselves.
+fn functions_equal(a: &ValueFun, b: &ValueFun) -> bool {
+    match (a, b) {
+        (ValueFun::Native(.., a), ValueFun::Native(.., b)) => Rc::ptr_eq(a, b),
+        (ValueFun::Const(.., a_body, a_env), ValueFun::Const(.., b_body, b_env)) => {
+            Rc::ptr_eq(a_body, b_body) && a_env.as_ptr() == b_env.as_ptr()
+        }
+        (ValueFun::Mutable(.., a_state), ValueFun::Mutable(.., b_state)) => {
+            Arc::ptr_eq(a_state, b_state)
+        }
+        _ => false,
+    }
+}
+
+/// Equality between values of the same type, it never casts.
+pub fn equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Ref(a), b) => equals(&a.read(), b),
+        (a, Value::Ref(b)) => equals(a, &b.read()),
+
+        (Value::Null, Value::Null) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Char(a), Value::Char(b)) => a == b,
+        (Value::I16(a), Value::I16(b)) => a == b,
+        (Value::F32(a), Value::F32(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::U8(a), Value::U8(b)) => a == b,
+        (Value::Usize(a), Value::Usize(b)) => a == b,
+        (Value::Tuple(a), Value::Tuple(b)) | (Value::List(a), Value::List(b)) => items_equal(a, b),
+        (Value::Map(a), Value::Map(b)) => maps_equal(a, b),
+        (Value::Fun(a), Value::Fun(b)) => functions_equal(a, b),
+        (Value::Object(a), Value::Object(b)) => match (a, b) {
+            (ValueObject::Native(a), ValueObject::Native(b)) => a == b,
+            (ValueObject::Instance(a_ty, a), ValueObject::Instance(b_ty, b)) => {
+                a_ty == b_ty && fields_equal(a, b)
+            }
+            (ValueObject::PropertyMap(a), ValueObject::PropertyMap(b)) => fields_equal(a, b),
+            (ValueObject::Variant(a_ty, a_name, a), ValueObject::Variant(b_ty, b_name, b)) => {
+                a_ty == b_ty && a_name == b_name && fields_equal(a, b)
+            }
+            _ => false,
         },
-        _ => todo!("{kind:?}"),
+        _ => false,
     }
 }
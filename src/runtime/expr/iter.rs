@@ -0,0 +1,169 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::{Arc, RwLock};
+
+use crate::runtime::{AmvmPropagate, AmvmVariable};
+use crate::tokens::{AmvmType, ValueFun, ValueObject, VariableKind};
+use crate::{
+    runtime::AmvmResult,
+    tokens::{AmvmScope, Value},
+};
+
+use super::range::RangeValues;
+
+pub type Values = Box<dyn Iterator<Item = Value>>;
+
+/// Values of the built-in iterables, `None` for values that need an
+/// iterator with a `next` function.
+///
+/// - Lists yield their items
+/// - Strings yield a char per Unicode scalar
+/// - Maps yield `(key, value)` tuples
+/// - Ranges yield each of their numbers
+/// - Objects yield `(name, value)` tuples, sorted by name
+pub fn values(value: &Value) -> Option<Result<Values, &'static str>> {
+    let values: Values = match value {
+        Value::Ref(var) => return values(&var.read()),
+        Value::List(values) => Box::new(values.clone().into_iter()),
+        Value::String(string) => Box::new(
+            string
+                .chars()
+                .map(Value::Char)
+                .collect::<Vec<_>>()
+                .into_iter(),
+        ),
+        Value::Map(map) => Box::new(
+            map.iter()
+                .map(|(key, value)| Value::Tuple(vec![key.to_value(), value.clone()]))
+                .collect::<Vec<_>>()
+                .into_iter(),
+        ),
+        Value::Object(object) => {
+            if let Some(range) = RangeValues::from_value(value) {
+                return Some(range.map(|range| Box::new(range) as Values));
+            }
+
+            let fields = match object {
+                ValueObject::Native(_) => return None,
+                ValueObject::Instance(_, fields)
+                | ValueObject::PropertyMap(fields)
+                | ValueObject::Variant(_, _, fields) => fields,
+            };
+            if fields.contains_key("next") {
+                return None;
+            }
+
+            let mut fields = fields.iter().collect::<Vec<_>>();
+            fields.sort_by_key(|(name, _)| *name);
+
+            Box::new(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| {
+                        Value::Tuple(vec![
+                            Value::String(name.clone()),
+                            value.read().unwrap().clone(),
+                        ])
+                    })
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )
+        }
+        _ => return None,
+    };
+
+    Some(Ok(values))
+}
+
+fn iterator_result(done: bool, value: Value) -> Value {
+    let mut obj = HashMap::new();
+    obj.insert(
+        String::from("done"),
+        Arc::new(RwLock::new(Value::Bool(done))),
+    );
+    obj.insert(String::from("value"), Arc::new(RwLock::new(value)));
+
+    Value::Object(ValueObject::Instance(
+        AmvmType::Named(Box::from("IteratorResult")),
+        obj,
+    ))
+}
+
+/// Standard `#Iterator` over the given values, returned by the `iter`
+/// property of every iterable.
+pub fn iterator(mut values: Values) -> Value {
+    from_fn(move |_| Ok(values.next()))
+}
+
+/// Standard `#Iterator` that gets each item from `next_value`, until it
+/// gives `None`.
+///
+/// Nothing is computed until the first call to `next`. The iterator keeps
+/// the last result in its `value` and `done` fields, `@for` knows it's a
+/// standard iterator because of the `done` field.
+pub fn from_fn<F>(mut next_value: F) -> Value
+where
+    F: FnMut(&mut AmvmScope) -> Result<Option<Value>, AmvmPropagate> + 'static,
+{
+    let iterate = move |scope: &mut AmvmScope| -> AmvmResult {
+        let s = scope.context.lock().unwrap();
+        let Some(iterator) = s.variables.get(&String::from("self")) else {
+            drop(s);
+            return Err(AmvmPropagate::Err(
+                scope.error("self is not defined. Please report this bug as E0001"),
+            ));
+        };
+        let iterator = iterator.read();
+        drop(s);
+
+        let Value::Object(ValueObject::Instance(_, fields)) = &*iterator else {
+            return Err(AmvmPropagate::Err(
+                scope.error("Iterator should be an object"),
+            ));
+        };
+
+        // Finished iterators stay finished
+        let next = match fields.get("done").map(|done| done.read().unwrap().clone()) {
+            Some(Value::Bool(true)) => None,
+            _ => next_value(scope)?,
+        };
+        let done = next.is_none();
+        let value = next.unwrap_or(Value::Null);
+
+        for (name, new_value) in [("value", value.clone()), ("done", Value::Bool(done))] {
+            if let Some(field) = fields.get(name) {
+                *field.write().unwrap() = new_value;
+            }
+        }
+
+        Ok(iterator_result(done, value))
+    };
+    let iterate = ValueFun::Native(
+        vec![(
+            Box::from("self"),
+            VariableKind::Mut,
+            AmvmType::Named(Box::from("Iterator")),
+        )],
+        AmvmType::Named(Box::from("IteratorResult")),
+        Rc::new(RefCell::new(iterate)),
+    );
+
+    let mut obj = HashMap::new();
+    obj.insert(String::from("value"), Arc::new(RwLock::new(Value::Null)));
+    obj.insert(
+        String::from("done"),
+        Arc::new(RwLock::new(Value::Bool(false))),
+    );
+    obj.insert(
+        String::from("next"),
+        Arc::new(RwLock::new(Value::Fun(iterate))),
+    );
+
+    let iterator = Value::Object(ValueObject::Instance(
+        AmvmType::Named(Box::from("Iterator")),
+        obj,
+    ));
+
+    Value::Ref(AmvmVariable::new(VariableKind::Mut, iterator))
+}
@@ -0,0 +1,35 @@
+use crate::{
+    runtime::{AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, CommandExpression, Value},
+};
+
+pub fn and(scope: &mut AmvmScope, a: &CommandExpression, b: &CommandExpression) -> AmvmResult {
+    if !boolean(scope, a)? {
+        return Ok(Value::Bool(false));
+    }
+
+    Ok(Value::Bool(boolean(scope, b)?))
+}
+
+pub fn or(scope: &mut AmvmScope, a: &CommandExpression, b: &CommandExpression) -> AmvmResult {
+    if boolean(scope, a)? {
+        return Ok(Value::Bool(true));
+    }
+
+    Ok(Value::Bool(boolean(scope, b)?))
+}
+
+pub fn not(scope: &mut AmvmScope, value: &CommandExpression) -> AmvmResult {
+    Ok(Value::Bool(!boolean(scope, value)?))
+}
+
+fn boolean(scope: &mut AmvmScope, value: &CommandExpression) -> Result<bool, AmvmPropagate> {
+    let value = super::eval(scope, value)?.as_value();
+    let Value::Bool(value) = value.as_ref() else {
+        return Err(AmvmPropagate::Err(
+            scope.error("Condition should be boolean"),
+        ));
+    };
+
+    Ok(*value)
+}
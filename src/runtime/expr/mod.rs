@@ -1,34 +1,84 @@
 use std::sync::Arc;
 
 use crate::{
-    runtime::{AmvmPropagate, AmvmVariable},
-    tokens::{AmvmScope, BinaryKind, CommandExpression, Value, VariableKind},
+    runtime::{commands, limits, AmvmPropagate, AmvmVariable},
+    tokens::{
+        AmvmScope, BinaryKind, CommandExpression, Value, ValueMap, ValueMapKey, VariableKind,
+    },
 };
 
 pub mod addition;
 pub mod binary_op;
 mod cond;
+pub mod iter;
+mod logical;
 pub mod property;
-mod range;
+pub mod range;
 mod r#struct;
+mod unary;
 mod value;
 mod var;
+mod variant;
 
 use binary_op::BinaryOpKind;
 
 pub fn eval(
     scope: &mut AmvmScope,
     expr: &CommandExpression,
+) -> Result<AmvmExprResult, AmvmPropagate> {
+    limits::charge(scope)?;
+    let result = eval_expr(scope, expr)?;
+    limits::check_value(scope, &result)?;
+
+    Ok(result)
+}
+
+fn eval_expr(
+    scope: &mut AmvmScope,
+    expr: &CommandExpression,
 ) -> Result<AmvmExprResult, AmvmPropagate> {
     match expr {
         CommandExpression::Binary(kind, a, b) => Ok(match kind {
             BinaryKind::Add => addition::eval(scope, a, b),
             BinaryKind::Sub => binary_op::eval(scope, BinaryOpKind::Sub, a, b),
             BinaryKind::Mult => binary_op::eval(scope, BinaryOpKind::Mult, a, b),
+            BinaryKind::Div => binary_op::eval(scope, BinaryOpKind::Div, a, b),
+            BinaryKind::Mod => binary_op::eval(scope, BinaryOpKind::Mod, a, b),
+            BinaryKind::Pow => binary_op::eval(scope, BinaryOpKind::Pow, a, b),
+            BinaryKind::BitAnd => binary_op::eval(scope, BinaryOpKind::BitAnd, a, b),
+            BinaryKind::BitOr => binary_op::eval(scope, BinaryOpKind::BitOr, a, b),
+            BinaryKind::BitXor => binary_op::eval(scope, BinaryOpKind::BitXor, a, b),
+            BinaryKind::Shl => binary_op::eval(scope, BinaryOpKind::Shl, a, b),
+            BinaryKind::Shr => binary_op::eval(scope, BinaryOpKind::Shr, a, b),
             _ => cond::eval(scope, kind, a, b),
         }?
         .into()),
 
+        CommandExpression::List(items) => Ok(Value::List(
+            items
+                .iter()
+                .map(|item| Ok(eval(scope, item)?.as_value().as_ref().clone()))
+                .collect::<Result<_, AmvmPropagate>>()?,
+        )
+        .into()),
+
+        CommandExpression::Map(entries) => {
+            let mut map = ValueMap::new();
+            for (key, value) in entries {
+                let key = eval(scope, key)?.as_value();
+                let Some(key) = ValueMapKey::from_value(&key) else {
+                    return Err(AmvmPropagate::Err(
+                        scope.error("Map keys should be numbers, strings, chars or booleans"),
+                    ));
+                };
+                let value = eval(scope, value)?.as_value();
+
+                map.insert(key, value.as_ref().clone());
+            }
+
+            Ok(Value::Map(map).into())
+        }
+
         CommandExpression::Prev => Ok(scope
             .context
             .lock()
@@ -40,19 +90,70 @@ pub fn eval(
             Ok(property::eval(scope, var, property)?.into())
         }
 
-        CommandExpression::Range(from, to) => Ok(range::eval(scope, from, to)?.into()),
-        CommandExpression::Ref(_, var) => {
-            let var = eval(scope, var)?.as_ref();
+        CommandExpression::OptionalProperty(var, property) => {
+            Ok(property::eval_optional(scope, var, property)?.into())
+        }
+        CommandExpression::And(a, b) => Ok(logical::and(scope, a, b)?.into()),
+        CommandExpression::Or(a, b) => Ok(logical::or(scope, a, b)?.into()),
+        CommandExpression::Not(value) => Ok(logical::not(scope, value)?.into()),
+        CommandExpression::Negate(value) => Ok(unary::negate(scope, value)?.into()),
+        CommandExpression::Coalesce(value, default) => {
+            let value = eval(scope, value)?;
+            if matches!(value.as_value().as_ref(), Value::Null) {
+                return eval(scope, default);
+            }
+
+            Ok(value)
+        }
 
-            Ok(match &*var.read() {
-                Value::Ref(v) => Value::Ref(v.clone()),
-                _ => Value::Ref(var),
+        CommandExpression::Range(from, to, step, inclusive) => {
+            Ok(range::eval(scope, from, to, step, *inclusive)?.into())
+        }
+        CommandExpression::Ref(kind, var) => {
+            let var = eval(scope, var)?;
+            if let Some(variable) = var.as_var() {
+                let context = scope.context.lock().unwrap();
+                if context.is_captured_variable(&variable) {
+                    drop(context);
+
+                    // Can be read, but it can't be written through the reference
+                    if *kind == VariableKind::Const {
+                        return Ok(Value::Ref(AmvmVariable::Const(var.as_value())).into());
+                    }
+
+                    return Err(AmvmPropagate::Err(
+                        scope.error("Const functions can't modify captured variables"),
+                    ));
+                }
             }
-            .into())
+
+            let var = var.as_ref();
+            let inner = var.with(|value| match value {
+                Value::Ref(v) => Some(v.clone()),
+                _ => None,
+            });
+
+            Ok(Value::Ref(inner.unwrap_or(var)).into())
         }
 
         CommandExpression::Struct(name, body) => Ok(r#struct::eval(scope, name, body)?.into()),
+        CommandExpression::Tuple(items) => Ok(Value::Tuple(
+            items
+                .iter()
+                .map(|item| Ok(eval(scope, item)?.as_value().as_ref().clone()))
+                .collect::<Result<_, AmvmPropagate>>()?,
+        )
+        .into()),
         CommandExpression::Value(v) => Ok(value::eval(scope, v)?.into()),
+        CommandExpression::Variant(ty, name, body) => {
+            Ok(variant::eval(scope, ty, name, body)?.into())
+        }
+        CommandExpression::Function(args, ret, body) => {
+            Ok(commands::function::create(scope, &[], args, ret, body).into())
+        }
+        CommandExpression::IsVariant(value, ty, name) => {
+            Ok(variant::is(scope, value, ty, name)?.into())
+        }
         CommandExpression::Var(v) => Ok(var::eval(scope, v)?.into()),
     }
 }
@@ -96,10 +197,26 @@ impl AmvmExprResult {
                 Value::Ref(var) => var.clone(),
                 _ => AmvmVariable::new(VariableKind::Const, Arc::as_ref(v).clone()),
             },
-            Self::Variable(var) => match &*var.read() {
-                Value::Ref(v) => v.clone(),
-                _ => var.clone(),
+            Self::Variable(var) => var
+                .with(|value| match value {
+                    Value::Ref(v) => Some(v.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| var.clone()),
+        }
+    }
+
+    /// Like [Self::as_value], but the value of a variable isn't copied.
+    pub fn with_value<T>(&self, f: impl FnOnce(&Value) -> T) -> T {
+        match self {
+            Self::Value(v) => match &**v {
+                Value::Ref(var) => var.with(f),
+                value => f(value),
             },
+            Self::Variable(var) => var.with(|value| match value {
+                Value::Ref(v) => v.with(f),
+                value => f(value),
+            }),
         }
     }
 
@@ -1,6 +1,6 @@
 use crate::{
     runtime::{expr, AmvmPropagate, AmvmResult},
-    tokens::{AmvmScope, CommandExpression, Value, ValueObject},
+    tokens::{AmvmScope, CommandExpression, Value, ValueMapKey, ValueObject},
 };
 
 pub fn eval(
@@ -8,30 +8,97 @@ pub fn eval(
     var: &CommandExpression,
     property: &CommandExpression,
 ) -> AmvmResult {
-    let var = expr::eval(scope, var)?.as_value();
-    let var = var.as_ref();
+    let var = expr::eval(scope, var)?;
     let property = expr::eval(scope, property)?.as_value();
-    let property = property.as_ref();
 
-    get(scope, var, property)
+    var.with_value(|var| get(scope, var, &property))
+}
+
+/// Like [eval], but a null value gives null instead of failing and
+/// the property isn't evaluated.
+pub fn eval_optional(
+    scope: &mut AmvmScope,
+    var: &CommandExpression,
+    property: &CommandExpression,
+) -> AmvmResult {
+    let var = expr::eval(scope, var)?;
+    if var.with_value(|var| matches!(var, Value::Null)) {
+        return Ok(Value::Null);
+    }
+
+    let property = expr::eval(scope, property)?.as_value();
+
+    var.with_value(|var| get(scope, var, &property))
+}
+
+/// Own properties and map keys named `iter` take precedence over the
+/// standard iterator.
+fn has_own(var: &Value, property: &str) -> bool {
+    match var {
+        Value::Map(map) => map.contains_key(&ValueMapKey::String(property.to_string())),
+        Value::Object(
+            ValueObject::Instance(_, map)
+            | ValueObject::PropertyMap(map)
+            | ValueObject::Variant(_, _, map),
+        ) => map.contains_key(property),
+        _ => false,
+    }
 }
 
 pub fn get(scope: &mut AmvmScope, var: &Value, property: &Value) -> AmvmResult {
+    if matches!(property, Value::String(p) if p == "iter") && !has_own(var, "iter") {
+        if let Some(values) = expr::iter::values(var) {
+            let values = values.map_err(|err| AmvmPropagate::Err(scope.error(err)))?;
+
+            return Ok(expr::iter::iterator(values));
+        }
+    }
+
     match var {
+        Value::Null => Err(AmvmPropagate::Err(
+            scope.error("Cannot access a property of null"),
+        )),
         Value::String(var) => match property {
             Value::String(prop) => match &prop as &str {
-                "length" => Ok(Value::U8(var.len() as u8)),
+                "length" => Ok(Value::Usize(var.len())),
                 _ => Ok(Value::Null),
             },
-            Value::U8(idx) => Ok(var
-                .chars()
-                .nth(*idx as usize)
-                .map_or(Value::Null, |c| Value::String(String::from(c)))),
-            _ => todo!(),
+            _ => match property.as_index() {
+                Some(idx) => Ok(var
+                    .chars()
+                    .nth(idx)
+                    .map_or(Value::Null, |c| Value::String(String::from(c)))),
+                None => Err(AmvmPropagate::Err(
+                    scope.error("Strings only can be accessed by a number or \"length\""),
+                )),
+            },
+        },
+        Value::List(values) => match property.as_index() {
+            Some(idx) => values
+                .get(idx)
+                .cloned()
+                .ok_or_else(|| AmvmPropagate::Err(scope.error("List index out of bounds"))),
+            None => Err(AmvmPropagate::Err(
+                scope.error("Lists only can be accessed by a number"),
+            )),
+        },
+        Value::Map(map) => Ok(ValueMapKey::from_value(property)
+            .and_then(|key| map.get(&key).cloned())
+            .unwrap_or(Value::Null)),
+        Value::Tuple(values) => match property.as_index() {
+            Some(idx) => values
+                .get(idx)
+                .cloned()
+                .ok_or_else(|| AmvmPropagate::Err(scope.error("Tuple index out of bounds"))),
+            None => Err(AmvmPropagate::Err(
+                scope.error("Tuples only can be accessed by a number"),
+            )),
         },
         Value::Object(value) => match value {
             ValueObject::Native(_) => todo!("Can't get properties of native object"),
-            ValueObject::Instance(_, map) | ValueObject::PropertyMap(map) => match property {
+            ValueObject::Instance(_, map)
+            | ValueObject::PropertyMap(map)
+            | ValueObject::Variant(_, _, map) => match property {
                 Value::String(name) => Ok(map
                     .get(name)
                     .map(|p| p.read().unwrap().clone())
@@ -1,192 +1,256 @@
-use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
-use crate::runtime::commands::builtin;
-use crate::runtime::{AmvmPropagate, AmvmVariable};
-use crate::tokens::{AmvmType, CommandExpression, ValueFun, ValueObject, VariableKind};
+use crate::runtime::AmvmPropagate;
+use crate::tokens::{AmvmType, CommandExpression, ValueObject};
 use crate::{
     runtime::AmvmResult,
     tokens::{AmvmScope, Value},
 };
 
-use super::binary_op::BinaryOpKind;
-
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 enum Kind {
     U8,
     I16,
+    Usize,
     F32,
+    Char,
 }
 
-pub fn eval(scope: &mut AmvmScope, from: &CommandExpression, to: &CommandExpression) -> AmvmResult {
-    let from = super::eval(scope, from)?.as_value();
-    let to = super::eval(scope, to)?.as_value();
-    let kind = match (&*from, &*to) {
-        (Value::Null, _) | (_, Value::Null) => {
-            return Err(AmvmPropagate::Err(scope.error("Null is not iterable")))
-        }
-        (Value::U8(_), Value::U8(_)) => Kind::U8,
-        (Value::U8(_), _) => {
-            return Err(AmvmPropagate::Err(
-                scope.error("Range should be the same type on both sides"),
-            ))
-        }
-        (Value::I16(_), Value::I16(_)) => Kind::I16,
-        (Value::I16(_), _) => {
-            return Err(AmvmPropagate::Err(
-                scope.error("Range should be the same type on both sides"),
-            ))
-        }
-        (Value::F32(_), Value::F32(_)) => Kind::F32,
-        (Value::F32(_), _) => {
-            return Err(AmvmPropagate::Err(
-                scope.error("Range should be the same type on both sides"),
-            ))
-        }
-        (_, _) => {
-            return Err(AmvmPropagate::Err(
-                scope.error("Range is only available for numbers"),
-            ))
-        }
-    };
-    let direction = match kind {
-        Kind::U8 => match (&*from, &*to) {
-            (Value::U8(a), Value::U8(b)) => a > b,
-            _ => unreachable!(),
-        },
-        Kind::I16 => match (&*from, &*to) {
-            (Value::I16(a), Value::I16(b)) => a > b,
-            _ => unreachable!(),
-        },
-        Kind::F32 => match (&*from, &*to) {
-            (Value::F32(a), Value::F32(b)) => a > b,
-            _ => unreachable!(),
-        },
+#[derive(Clone, Copy)]
+enum Bounds {
+    Int { start: i128, end: i128, step: i128 },
+    Float { start: f64, end: f64, step: f64 },
+}
+
+/// Values of a `#Range`. They are computed from the start and the number of
+/// steps taken, so ranges of floats don't accumulate rounding errors.
+pub struct RangeValues {
+    kind: Kind,
+    bounds: Bounds,
+    inclusive: bool,
+    descending: bool,
+    index: u32,
+}
+
+fn number(value: &Value) -> Option<(Kind, Bounds)> {
+    let int = |v: i128| Bounds::Int {
+        start: v,
+        end: v,
+        step: 1,
     };
 
-    let iterate = move |scope: &mut AmvmScope| -> AmvmResult {
-        let to = to.clone();
+    match value {
+        Value::U8(v) => Some((Kind::U8, int(*v as i128))),
+        Value::I16(v) => Some((Kind::I16, int(*v as i128))),
+        Value::Usize(v) => Some((Kind::Usize, int(*v as i128))),
+        Value::Char(v) => Some((Kind::Char, int(*v as u32 as i128))),
+        Value::F32(v) => Some((
+            Kind::F32,
+            Bounds::Float {
+                start: *v as f64,
+                end: *v as f64,
+                step: 1.,
+            },
+        )),
+        _ => None,
+    }
+}
+
+impl RangeValues {
+    pub fn new(
+        start: &Value,
+        end: &Value,
+        step: &Value,
+        inclusive: bool,
+    ) -> Result<Self, &'static str> {
+        if matches!(start, Value::Null) || matches!(end, Value::Null) {
+            return Err("Null is not iterable");
+        }
 
-        let s = scope.context.lock().unwrap();
-        let Some(iterator) = s.variables.get(&String::from("self")) else {
-            drop(s);
-            return Err(AmvmPropagate::Err(
-                scope.error("self is not defined. Please report this bug as E0001"),
-            ));
+        let (Some((kind, start)), Some((end_kind, end))) = (number(start), number(end)) else {
+            return Err("Range is only available for numbers and chars");
         };
-        let iterator = iterator.clone();
-        drop(s);
-
-        let value_ref = builtin::call(
-            scope,
-            ".obj.mut_access",
-            &[
-                iterator.clone().into(),
-                Value::String(String::from("value")).into(),
-            ],
-        )?
-        .expect(".obj.mut_access returns the reference");
-
-        let return_value = match (kind, &*value_ref.as_value(), &*to) {
-            (Kind::U8, Value::U8(value), Value::U8(to)) => {
-                if value >= to {
-                    Some(Value::U8(value.clone()))
-                } else {
-                    None
+        if kind != end_kind {
+            return Err("Range should be the same type on both sides");
+        }
+
+        let step = match step {
+            Value::Null => None,
+            step => match number(step) {
+                // Chars advance by any integer amount
+                Some((Kind::U8 | Kind::I16 | Kind::Usize, step)) if kind == Kind::Char => {
+                    Some(step)
                 }
-            }
-            (Kind::I16, Value::I16(value), Value::I16(to)) => {
-                if value >= to {
-                    Some(Value::I16(value.clone()))
-                } else {
-                    None
+                Some((step_kind, step)) if step_kind == kind => Some(step),
+                _ => return Err("Range step should be the same type as its bounds"),
+            },
+        };
+
+        let bounds = match (start, end, step) {
+            (Bounds::Int { start, .. }, Bounds::Int { start: end, .. }, step) => {
+                let step = match step {
+                    Some(Bounds::Int { start: step, .. }) => step,
+                    _ => 1,
+                };
+                if step <= 0 {
+                    return Err("Range step should be greater than zero");
                 }
+
+                Bounds::Int { start, end, step }
             }
-            (Kind::F32, Value::F32(value), Value::F32(to)) => {
-                if value >= to {
-                    Some(Value::F32(value.clone()))
-                } else {
-                    None
+            (Bounds::Float { start, .. }, Bounds::Float { start: end, .. }, step) => {
+                let step = match step {
+                    Some(Bounds::Float { start: step, .. }) => step,
+                    _ => 1.,
+                };
+                if step.is_nan() || step <= 0. {
+                    return Err("Range step should be greater than zero");
                 }
+
+                Bounds::Float { start, end, step }
             }
             _ => unreachable!(),
         };
 
-        if let Some(val) = return_value {
-            let mut obj = HashMap::new();
-            obj.insert(
-                String::from("done"),
-                Arc::new(RwLock::new(Value::Bool(true))),
-            );
-            obj.insert(String::from("value"), Arc::new(RwLock::new(val)));
-
-            return Ok(Value::Object(ValueObject::Instance(
-                AmvmType::Named(Box::from("IteratorResult")),
-                obj,
-            )));
-        }
+        let descending = match bounds {
+            Bounds::Int { start, end, .. } => start > end,
+            Bounds::Float { start, end, .. } => start > end,
+        };
 
-        let value_diff = match kind {
-            Kind::U8 => Value::U8(1),
-            Kind::I16 => Value::I16(1),
-            Kind::F32 => Value::F32(1.),
+        Ok(Self {
+            kind,
+            bounds,
+            inclusive,
+            descending,
+            index: 0,
+        })
+    }
+
+    /// Values of a `#Range` instance, `None` for any other value. Structs
+    /// named `#Range` with a `next` function are iterators instead.
+    pub fn from_value(value: &Value) -> Option<Result<Self, &'static str>> {
+        let Value::Object(ValueObject::Instance(AmvmType::Named(name), fields)) = value else {
+            return None;
         };
-        let new_value = if direction {
-            super::binary_op::eval_post(
-                scope,
-                BinaryOpKind::Sub,
-                &*value_ref.as_value(),
-                &value_diff,
-            )?
-        } else {
-            super::addition::eval_strict(scope, &*value_ref.as_value(), &value_diff)?
+        if &**name != "Range" || fields.contains_key("next") {
+            return None;
+        }
+
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .map(|value| value.read().unwrap().clone())
+                .unwrap_or(Value::Null)
         };
-        let variable = value_ref.as_ref();
-        let mut variable = variable.write().expect("Should be mutable"); // TODO: Error propagation
-        *variable = new_value.clone();
-
-        let mut obj = HashMap::new();
-        obj.insert(
-            String::from("done"),
-            Arc::new(RwLock::new(Value::Bool(false))),
-        );
-        obj.insert(
-            String::from("value"),
-            Arc::new(RwLock::new(new_value.clone())),
-        );
-
-        Ok(Value::Object(ValueObject::Instance(
-            AmvmType::Named(Box::from("IteratorResult")),
-            obj,
-        )))
+        let inclusive = matches!(field("inclusive"), Value::Bool(true));
+
+        Some(Self::new(
+            &field("start"),
+            &field("end"),
+            &field("step"),
+            inclusive,
+        ))
+    }
+
+    fn contains<T: PartialOrd>(&self, value: T, end: T) -> bool {
+        match (self.descending, self.inclusive) {
+            (false, false) => value < end,
+            (false, true) => value <= end,
+            (true, false) => value > end,
+            (true, true) => value >= end,
+        }
+    }
+}
+
+impl Iterator for RangeValues {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let index = self.index;
+            self.index = self.index.checked_add(1)?;
+
+            let value = match self.bounds {
+                Bounds::Int { start, end, step } => {
+                    let offset = step * index as i128;
+                    let value = if self.descending {
+                        start - offset
+                    } else {
+                        start + offset
+                    };
+                    if !self.contains(value, end) {
+                        return None;
+                    }
+
+                    match self.kind {
+                        Kind::U8 => Value::U8(value as u8),
+                        Kind::I16 => Value::I16(value as i16),
+                        Kind::Usize => Value::Usize(value as usize),
+                        // Surrogates are not chars, they are skipped
+                        Kind::Char => match char::from_u32(value as u32) {
+                            Some(value) => Value::Char(value),
+                            None => continue,
+                        },
+                        Kind::F32 => unreachable!(),
+                    }
+                }
+                Bounds::Float { start, end, step } => {
+                    let offset = step * index as f64;
+                    let value = if self.descending {
+                        start - offset
+                    } else {
+                        start + offset
+                    };
+                    if !self.contains(value, end) {
+                        return None;
+                    }
+
+                    Value::F32(value as f32)
+                }
+            };
+
+            return Some(value);
+        }
+    }
+}
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    from: &CommandExpression,
+    to: &CommandExpression,
+    step: &Option<Box<CommandExpression>>,
+    inclusive: bool,
+) -> AmvmResult {
+    let from = super::eval(scope, from)?.as_value();
+    let to = super::eval(scope, to)?.as_value();
+    let step = match step {
+        Some(step) => super::eval(scope, step)?.as_value().as_ref().clone(),
+        None => Value::Null,
     };
-    let iterate = ValueFun::Native(
-        vec![(
-            Box::from("self"),
-            VariableKind::Mut,
-            AmvmType::Named(Box::from("Iterator")),
-        )],
-        AmvmType::Named(Box::from("IteratorResult")),
-        Rc::new(RefCell::new(iterate)),
-    );
+
+    // Check it now, so errors point to where the range is created
+    if let Err(err) = RangeValues::new(&from, &to, &step, inclusive) {
+        return Err(AmvmPropagate::Err(scope.error(err)));
+    }
 
     let mut obj = HashMap::new();
     obj.insert(
-        String::from("value"),
-        Arc::new(RwLock::new((&*from).clone())),
+        String::from("start"),
+        Arc::new(RwLock::new(from.as_ref().clone())),
     );
     obj.insert(
-        String::from("next"),
-        Arc::new(RwLock::new(Value::Fun(iterate))),
+        String::from("end"),
+        Arc::new(RwLock::new(to.as_ref().clone())),
+    );
+    obj.insert(String::from("step"), Arc::new(RwLock::new(step)));
+    obj.insert(
+        String::from("inclusive"),
+        Arc::new(RwLock::new(Value::Bool(inclusive))),
     );
 
-    let iterator = Value::Object(ValueObject::Instance(
-        AmvmType::Named(Box::from("Iterator")),
+    Ok(Value::Object(ValueObject::Instance(
+        AmvmType::Named(Box::from("Range")),
         obj,
-    ));
-    let iterator = AmvmVariable::new(VariableKind::Mut, iterator);
-
-    Ok(Value::Ref(iterator))
+    )))
 }
@@ -2,8 +2,8 @@ use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 use crate::{
-    runtime::{expr, AmvmResult},
-    tokens::{AmvmScope, AmvmType, CommandExpression, Value, ValueObject},
+    runtime::{expr, types, AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, AmvmType, AmvmTypeDefinition, CommandExpression, Value, ValueObject},
 };
 
 pub fn eval(
@@ -11,19 +11,85 @@ pub fn eval(
     ty: &AmvmType,
     body: &Vec<(Box<str>, CommandExpression)>,
 ) -> AmvmResult {
+    let decl = ty
+        .name()
+        .and_then(|name| scope.context.lock().unwrap().get_struct(name));
+    if let Some(AmvmTypeDefinition::Enum { .. }) = &decl {
+        return Err(AmvmPropagate::Err(
+            scope.error("Enums are built from one of their variants"),
+        ));
+    }
+    let mut generics = match &decl {
+        Some(decl) => types::apply_generics(scope, decl, ty)?,
+        None => types::AmvmGenerics::new(),
+    };
+    let fields = match &decl {
+        Some(decl) => types::fields_of(scope, decl),
+        None => vec![],
+    };
+
+    if let Some(AmvmTypeDefinition::Inheritance(parents, _)) = &decl {
+        let inherited = types::inherited_fields(scope, parents);
+        if inherited
+            .iter()
+            .any(|(name, _)| !body.iter().any(|(prop_name, _)| prop_name == name))
+        {
+            return Err(AmvmPropagate::Err(scope.error("Missing inherited field")));
+        }
+    }
+
     let mut body_evaluated = HashMap::new();
 
     for (prop_name, prop_value) in body {
-        let prop_name = prop_name.to_string();
         let prop_value = expr::eval(scope, prop_value)?.as_value();
         let prop_value = &*prop_value;
+
+        let field = fields.iter().find(|field| field.0 == *prop_name);
+        if let Some((_, field_type)) = field {
+            if !types::check(scope, prop_value, field_type, &mut generics) {
+                return Err(AmvmPropagate::Err(
+                    scope.error("Struct field doesn't match its declared type"),
+                ));
+            }
+        }
+
+        let prop_name = prop_name.to_string();
         let prop_value = Arc::new(RwLock::new(prop_value.clone()));
 
         body_evaluated.insert(prop_name, prop_value);
     }
 
     Ok(Value::Object(ValueObject::Instance(
-        ty.clone(),
+        instance_type(ty, decl.as_ref(), &generics),
         body_evaluated,
     )))
 }
+
+/// Type of the instance, with the generics given or inferred from its
+/// fields so it can be checked against them later.
+fn instance_type(
+    ty: &AmvmType,
+    decl: Option<&AmvmTypeDefinition>,
+    generics: &types::AmvmGenerics,
+) -> AmvmType {
+    let (Some(name), Some(params)) = (ty.name(), decl.and_then(AmvmTypeDefinition::generics))
+    else {
+        return ty.clone();
+    };
+    if params.is_empty() {
+        return ty.clone();
+    }
+
+    let args = params
+        .iter()
+        .map(|(param, _)| {
+            generics
+                .get(param)
+                .cloned()
+                .flatten()
+                .unwrap_or(AmvmType::Anonymous)
+        })
+        .collect();
+
+    AmvmType::Generic(Box::from(name), args)
+}
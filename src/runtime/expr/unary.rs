@@ -0,0 +1,22 @@
+use crate::{
+    runtime::{AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, CommandExpression, Value},
+};
+
+pub fn negate(scope: &mut AmvmScope, value: &CommandExpression) -> AmvmResult {
+    let value = super::eval(scope, value)?.as_value();
+
+    match value.as_ref() {
+        Value::I16(v) => v
+            .checked_neg()
+            .map(Value::I16)
+            .ok_or_else(|| AmvmPropagate::Err(scope.error("Attempt to negate with overflow"))),
+        Value::F32(v) => Ok(Value::F32(-v)),
+        Value::U8(_) | Value::Usize(_) => Err(AmvmPropagate::Err(
+            scope.error("Unsigned numbers can't be negated"),
+        )),
+        _ => Err(AmvmPropagate::Err(
+            scope.error("Only numbers can be negated"),
+        )),
+    }
+}
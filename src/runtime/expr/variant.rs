@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    runtime::{expr, types, AmvmPropagate, AmvmResult},
+    tokens::{AmvmScope, AmvmType, AmvmTypeDefinition, CommandExpression, Value, ValueObject},
+};
+
+pub fn eval(
+    scope: &mut AmvmScope,
+    ty: &AmvmType,
+    variant: &str,
+    body: &[(Box<str>, CommandExpression)],
+) -> AmvmResult {
+    let decl = ty
+        .name()
+        .and_then(|name| scope.context.lock().unwrap().get_struct(name));
+    let Some(AmvmTypeDefinition::Enum { variants }) = decl else {
+        return Err(AmvmPropagate::Err(scope.error("Enum is not declared")));
+    };
+    let Some((_, fields)) = variants.iter().find(|v| v.0.as_ref() == variant) else {
+        return Err(AmvmPropagate::Err(scope.error("Unknown enum variant")));
+    };
+
+    if fields
+        .iter()
+        .any(|(name, _)| !body.iter().any(|(prop_name, _)| prop_name == name))
+    {
+        return Err(AmvmPropagate::Err(scope.error("Missing variant field")));
+    }
+
+    let mut body_evaluated = HashMap::new();
+
+    for (prop_name, prop_value) in body {
+        let prop_value = expr::eval(scope, prop_value)?.as_value();
+        let prop_value = &*prop_value;
+
+        let Some((_, field_type)) = fields.iter().find(|field| field.0 == *prop_name) else {
+            return Err(AmvmPropagate::Err(scope.error("Unknown variant field")));
+        };
+        if !types::check(
+            scope,
+            prop_value,
+            field_type,
+            &mut types::AmvmGenerics::new(),
+        ) {
+            return Err(AmvmPropagate::Err(
+                scope.error("Variant field doesn't match its declared type"),
+            ));
+        }
+
+        let prop_name = prop_name.to_string();
+        let prop_value = Arc::new(RwLock::new(prop_value.clone()));
+
+        body_evaluated.insert(prop_name, prop_value);
+    }
+
+    Ok(Value::Object(ValueObject::Variant(
+        ty.clone(),
+        Box::from(variant),
+        body_evaluated,
+    )))
+}
+
+/// Check the tag of an enum value.
+pub fn is(
+    scope: &mut AmvmScope,
+    value: &CommandExpression,
+    ty: &AmvmType,
+    variant: &str,
+) -> AmvmResult {
+    let value = expr::eval(scope, value)?.as_value();
+
+    let is_variant = match value.as_ref() {
+        Value::Object(ValueObject::Variant(value_ty, value_variant, _)) => {
+            value_ty.name() == ty.name() && value_variant.as_ref() == variant
+        }
+        _ => false,
+    };
+
+    Ok(Value::Bool(is_variant))
+}
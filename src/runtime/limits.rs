@@ -0,0 +1,252 @@
+//! Limits of the resources a run can use, set in [RuntimeOptions].
+//!
+//! Fuel is counted on every command and expression, the other limits are
+//! checked every [CHECK_INTERVAL] units of fuel. Once a limit is exceeded
+//! everything that is evaluated fails with it, so the run stops even
+//! through `@finally` blocks.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::Instant;
+use std::{fmt, mem};
+
+use crate::runtime::{AmvmExprResult, AmvmPropagate, AmvmVariable, Context, RuntimeOptions};
+use crate::tokens::{AmvmScope, Value, ValueObject};
+
+/// Fuel used between the checks of time, heap and variables
+const CHECK_INTERVAL: u64 = 256;
+
+/// Limit that stopped a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmvmLimit {
+    Fuel,
+    Timeout,
+    Heap,
+    Variables,
+}
+
+impl fmt::Display for AmvmLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Fuel => "Out of fuel",
+            Self::Timeout => "Time limit exceeded",
+            Self::Heap => "Heap limit exceeded",
+            Self::Variables => "Too many variables",
+        })
+    }
+}
+
+/// Resources used by a run, shared by all its scopes
+#[derive(Debug, Default)]
+pub struct Usage {
+    fuel: Cell<u64>,
+    /// Calls being run, wherever they were made from
+    calls: Cell<usize>,
+    deadline: Cell<Option<Instant>>,
+    exceeded: Cell<Option<AmvmLimit>>,
+    /// Contexts where live variables can be, only kept when the heap or
+    /// the variables are limited
+    contexts: RefCell<Vec<Weak<Mutex<Context>>>>,
+}
+
+impl Usage {
+    /// Start counting the time of the run
+    pub fn start(&self, options: &RuntimeOptions) {
+        self.deadline
+            .set(options.timeout.map(|timeout| Instant::now() + timeout));
+    }
+
+    /// Fuel used so far
+    pub fn fuel(&self) -> u64 {
+        self.fuel.get()
+    }
+
+    /// Calls being run on the Rust stack
+    pub fn calls(&self) -> usize {
+        self.calls.get()
+    }
+
+    /// Time when the run should stop
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline.get()
+    }
+
+    /// Keep track of a new context, for the limits that look at variables.
+    pub fn register(&self, options: &RuntimeOptions, context: &Arc<Mutex<Context>>) {
+        if options.max_heap.is_some() || options.max_variables.is_some() {
+            self.contexts.borrow_mut().push(Arc::downgrade(context));
+        }
+    }
+
+    fn stop(&self, limit: AmvmLimit) -> Result<(), AmvmPropagate> {
+        self.exceeded.set(Some(limit));
+        Err(AmvmPropagate::Limit(limit))
+    }
+}
+
+/// Call being run, counted until it's dropped.
+pub struct Call(Rc<Usage>);
+
+impl Call {
+    /// Count a call, failing when it goes over the maximum depth.
+    pub fn enter(scope: &mut AmvmScope) -> Result<Self, AmvmPropagate> {
+        let usage = &scope.usage;
+        if usage.calls.get() >= scope.options.max_call_depth {
+            return Err(AmvmPropagate::Err(scope.error("Stack overflow")));
+        }
+
+        usage.calls.set(usage.calls.get() + 1);
+        Ok(Self(Rc::clone(usage)))
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        self.0.calls.set(self.0.calls.get() - 1);
+    }
+}
+
+/// Use a unit of fuel, and check the other limits from time to time.
+pub fn charge(scope: &AmvmScope) -> Result<(), AmvmPropagate> {
+    let usage = &scope.usage;
+    if let Some(limit) = usage.exceeded.get() {
+        return Err(AmvmPropagate::Limit(limit));
+    }
+
+    let fuel = usage.fuel.get() + 1;
+    usage.fuel.set(fuel);
+
+    let options = &scope.options;
+    if options.fuel.is_some_and(|max| fuel > max) {
+        return usage.stop(AmvmLimit::Fuel);
+    }
+
+    if fuel.is_multiple_of(CHECK_INTERVAL) {
+        check(scope)?;
+    }
+
+    Ok(())
+}
+
+/// Check every limit but the fuel.
+pub fn check(scope: &AmvmScope) -> Result<(), AmvmPropagate> {
+    let usage = &scope.usage;
+    if let Some(limit) = usage.exceeded.get() {
+        return Err(AmvmPropagate::Limit(limit));
+    }
+
+    if usage.deadline().is_some_and(|at| Instant::now() >= at) {
+        return usage.stop(AmvmLimit::Timeout);
+    }
+
+    let options = &scope.options;
+    if options.max_heap.is_none() && options.max_variables.is_none() {
+        return Ok(());
+    }
+
+    let (variables, heap) = live_variables(usage);
+    if options.max_variables.is_some_and(|max| variables > max) {
+        return usage.stop(AmvmLimit::Variables);
+    }
+    if options.max_heap.is_some_and(|max| heap > max) {
+        return usage.stop(AmvmLimit::Heap);
+    }
+
+    Ok(())
+}
+
+/// A single value bigger than the whole heap stops the run right away,
+/// so it can't grow too much between the checks.
+pub fn check_value(scope: &AmvmScope, result: &AmvmExprResult) -> Result<(), AmvmPropagate> {
+    let (Some(max), AmvmExprResult::Value(value)) = (scope.options.max_heap, result) else {
+        return Ok(());
+    };
+
+    // Only the outer size, it's checked often
+    let size = match &**value {
+        Value::String(string) => string.len(),
+        Value::List(values) | Value::Tuple(values) => values.len() * mem::size_of::<Value>(),
+        Value::Map(map) => map.len() * 2 * mem::size_of::<Value>(),
+        _ => 0,
+    };
+    if size > max {
+        return scope.usage.stop(AmvmLimit::Heap);
+    }
+
+    Ok(())
+}
+
+/// Number of variables of the live contexts, and an estimate of the size
+/// of their values. Values shared by several variables count once.
+fn live_variables(usage: &Usage) -> (usize, usize) {
+    let mut contexts = usage.contexts.borrow_mut();
+    contexts.retain(|context| context.strong_count() > 0);
+
+    let mut seen = HashSet::new();
+    let mut variables = 0;
+    let mut heap = 0;
+    for context in contexts.iter().filter_map(Weak::upgrade) {
+        // Contexts in use are skipped, they're counted next time
+        let Ok(context) = context.try_lock() else {
+            continue;
+        };
+
+        variables += context.variables.len();
+        for (name, variable) in &context.variables {
+            heap += name.len();
+            heap += match variable {
+                AmvmVariable::Const(value) => {
+                    if seen.insert(Arc::as_ptr(value) as usize) {
+                        value_size(value, &mut seen)
+                    } else {
+                        0
+                    }
+                }
+                AmvmVariable::Mut(value) | AmvmVariable::Let(value) | AmvmVariable::Var(value) => {
+                    shared_size(value, &mut seen)
+                }
+            };
+        }
+    }
+
+    (variables, heap)
+}
+
+fn shared_size(value: &Arc<RwLock<Value>>, seen: &mut HashSet<usize>) -> usize {
+    if !seen.insert(Arc::as_ptr(value) as usize) {
+        return 0;
+    }
+
+    match value.try_read() {
+        Ok(value) => value_size(&value, seen),
+        Err(_) => mem::size_of::<Value>(),
+    }
+}
+
+/// Estimate of the memory used by a value. References and functions only
+/// count themselves, what they point to is counted where it's declared.
+fn value_size(value: &Value, seen: &mut HashSet<usize>) -> usize {
+    let children = match value {
+        Value::String(string) => string.len(),
+        Value::List(values) | Value::Tuple(values) => {
+            values.iter().map(|value| value_size(value, seen)).sum()
+        }
+        Value::Map(map) => map
+            .iter()
+            .map(|(key, value)| value_size(&key.to_value(), seen) + value_size(value, seen))
+            .sum(),
+        Value::Object(
+            ValueObject::Instance(_, fields)
+            | ValueObject::PropertyMap(fields)
+            | ValueObject::Variant(_, _, fields),
+        ) => fields
+            .iter()
+            .map(|(name, value)| name.len() + shared_size(value, seen))
+            .sum(),
+        _ => 0,
+    };
+
+    mem::size_of::<Value>() + children
+}
@@ -0,0 +1,604 @@
+//! Suspendable execution.
+//!
+//! Commands are usually evaluated recursively, on the Rust stack. Bodies
+//! that stop in the middle and continue later, generators and tasks, are
+//! run by a [Machine] instead: the blocks and calls being run are kept in a stack
+//! of frames, so it can suspend after any command and resume from there.
+//!
+//! Other commands are evaluated as usual, so they can't suspend it. A
+//! machine dropped while it's suspended doesn't run the rest of its body,
+//! `@finally` blocks included.
+
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::runtime::commands::r#loop::{self, Flow};
+use crate::runtime::commands::{
+    self, builtin, call, conditional, r#for, r#match, r#try, r#while, r#yield,
+};
+use crate::runtime::tasks::{self, Awaited};
+use crate::runtime::{expr, limits, scope, types, AmvmPropagate, AmvmResult, AmvmVariable};
+use crate::tokens::{AmvmScope, AmvmType, Command, CommandExpression, Value, ValueFun};
+
+/// Why a machine stopped before finishing
+pub enum Suspend {
+    /// A generator gives a value
+    Yield(Value),
+    /// A task waits for the task with this id to finish
+    Await(usize),
+    /// A task sleeps until this time
+    Sleep(Instant),
+}
+
+/// State of a machine after resuming it
+pub enum State {
+    Suspended(Suspend),
+    Finished(AmvmResult),
+}
+
+/// What runs after the body of a frame finishes.
+enum Kind {
+    Block,
+    /// Body of a function, its value is given to the frame below
+    Call {
+        /// `None` when the value isn't checked
+        ret: Option<AmvmType>,
+        generics: types::AmvmGenerics,
+    },
+    Loop {
+        label: Option<Box<str>>,
+    },
+    While {
+        label: Option<Box<str>>,
+        condition: CommandExpression,
+        is_do: bool,
+    },
+    For {
+        label: Option<Box<str>>,
+        var: Box<str>,
+        source: r#for::Source,
+    },
+    Try {
+        catch: Option<(Box<str>, Vec<Command>)>,
+        finally: Option<Vec<Command>>,
+        phase: Phase,
+    },
+}
+
+/// Body of a `@try` being run
+enum Phase {
+    Body,
+    Catch,
+    /// With the result of the body or the catch
+    Finally(AmvmResult),
+}
+
+struct Frame {
+    scope: AmvmScope,
+    /// Position of the next command of the body
+    next: usize,
+    kind: Kind,
+}
+
+impl Frame {
+    fn new(scope: AmvmScope, kind: Kind) -> Self {
+        Self {
+            scope,
+            next: 0,
+            kind,
+        }
+    }
+}
+
+/// Result of running a command
+enum Step {
+    Done,
+    /// Its body is the new top frame
+    Pushed,
+    Suspend(Suspend),
+}
+
+/// Call that starts a task, from the scope where it was spawned
+struct Start {
+    caller: AmvmScope,
+    name: Box<str>,
+    fun: ValueFun,
+    args: Vec<AmvmVariable>,
+}
+
+/// What a machine runs, and how it can suspend
+enum Role {
+    Generator {
+        /// Type of the yielded values
+        item: AmvmType,
+        generics: types::AmvmGenerics,
+    },
+    /// `start` is taken the first time it's resumed
+    Task { start: Option<Start> },
+}
+
+pub struct Machine {
+    frames: Vec<Frame>,
+    /// Call frames, they count for the maximum call depth while running
+    calls: usize,
+    role: Role,
+    /// Task awaited by the top frame, its result is given when resuming
+    awaiting: Option<usize>,
+}
+
+impl Machine {
+    /// Machine for a call to a generator, `inner` is the scope where its
+    /// body runs.
+    pub fn generator(inner: AmvmScope, item: AmvmType, generics: types::AmvmGenerics) -> Self {
+        let kind = Kind::Call {
+            ret: None,
+            generics: generics.clone(),
+        };
+
+        Self {
+            frames: vec![Frame::new(inner, kind)],
+            calls: 1,
+            role: Role::Generator { item, generics },
+            awaiting: None,
+        }
+    }
+
+    /// Machine for a task that calls `fun` from `caller`, the call is only
+    /// done when it's resumed.
+    pub fn task(caller: AmvmScope, name: Box<str>, fun: ValueFun, args: Vec<AmvmVariable>) -> Self {
+        let start = Start {
+            caller,
+            name,
+            fun,
+            args,
+        };
+
+        Self {
+            frames: vec![],
+            calls: 0,
+            role: Role::Task { start: Some(start) },
+            awaiting: None,
+        }
+    }
+
+    /// Run until the body suspends or finishes.
+    pub fn resume(&mut self) -> State {
+        if let Role::Task { start } = &mut self.role {
+            if let Some(result) = start.take().and_then(|start| self.start(start)) {
+                return State::Finished(result);
+            }
+        }
+
+        if let Some(id) = self.awaiting.take() {
+            let frame = self.frames.last_mut().expect("Awaiting from a frame");
+            match tasks::result(&frame.scope, id) {
+                Ok(value) => frame.scope.context.lock().unwrap().push_prev_value(value),
+                Err(err) => {
+                    if let Some(result) = self.complete(Err(err)) {
+                        return State::Finished(result);
+                    }
+                }
+            }
+        }
+
+        loop {
+            let Some(frame) = self.frames.last_mut() else {
+                return State::Finished(Ok(Value::Null));
+            };
+
+            let body = Rc::clone(&frame.scope.body);
+            let Some(cmd) = body.get(frame.next) else {
+                match self.complete(Ok(Value::Null)) {
+                    Some(result) => return State::Finished(result),
+                    None => continue,
+                }
+            };
+            frame.next += 1;
+
+            let step = self.run(cmd);
+
+            // Like `commands::eval`, meta is only kept for the next command
+            if !matches!(step, Ok(Step::Pushed)) && !matches!(cmd, Command::Meta { .. }) {
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.scope.meta = None;
+                }
+            }
+
+            match step {
+                Ok(Step::Done | Step::Pushed) => {}
+                Ok(Step::Suspend(suspend)) => return State::Suspended(suspend),
+                Err(err) => {
+                    if let Some(result) = self.complete(Err(err)) {
+                        return State::Finished(result);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Run a command of the top frame. Commands with a body push it as a
+    /// new frame, the others are evaluated right away.
+    fn run(&mut self, cmd: &Command) -> Result<Step, AmvmPropagate> {
+        let scope = &mut self
+            .frames
+            .last_mut()
+            .expect("Commands run in a frame")
+            .scope;
+
+        let (inner, kind) = match cmd {
+            Command::Call { name, args } => {
+                limits::charge(scope)?;
+                return self.call(name, args);
+            }
+            Command::Conditional {
+                condition,
+                body,
+                otherwise,
+            } => {
+                limits::charge(scope)?;
+                match conditional::branch(scope, condition, body, otherwise)? {
+                    Some(body) => (scope.create_sub(body.clone()), Kind::Block),
+                    None => return Ok(Step::Done),
+                }
+            }
+            Command::For {
+                label,
+                var,
+                iterator,
+                body,
+            } => {
+                limits::charge(scope)?;
+                let mut source = r#for::Source::new(scope, iterator)?;
+                let body = Rc::new(body.clone());
+                let Some(inner) = r#for::run(scope, &body, var, &mut source)? else {
+                    return Ok(Step::Done);
+                };
+
+                let kind = Kind::For {
+                    label: label.clone(),
+                    var: var.clone(),
+                    source,
+                };
+                (inner, kind)
+            }
+            Command::Loop { label, body } => {
+                limits::charge(scope)?;
+
+                let kind = Kind::Loop {
+                    label: label.clone(),
+                };
+                (r#loop::run(scope, &Rc::new(body.clone()))?, kind)
+            }
+            Command::Match { value, arms } => {
+                limits::charge(scope)?;
+                match r#match::select(scope, value, arms)? {
+                    Some(inner) => (inner, Kind::Block),
+                    None => return Ok(Step::Done),
+                }
+            }
+            Command::Scope { body } => {
+                limits::charge(scope)?;
+                (scope.create_sub(body.clone()), Kind::Block)
+            }
+            Command::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                limits::charge(scope)?;
+
+                let kind = Kind::Try {
+                    catch: catch.clone(),
+                    finally: finally.clone(),
+                    phase: Phase::Body,
+                };
+                (scope.create_sub(body.clone()), kind)
+            }
+            Command::While {
+                label,
+                condition,
+                body,
+                is_do,
+            } => {
+                limits::charge(scope)?;
+                let body = Rc::new(body.clone());
+                let Some(inner) = r#while::run(scope, &body, condition, *is_do)? else {
+                    return Ok(Step::Done);
+                };
+
+                let kind = Kind::While {
+                    label: label.clone(),
+                    condition: condition.clone(),
+                    is_do: *is_do,
+                };
+                (inner, kind)
+            }
+            Command::Yield { value } => {
+                let Role::Generator { item, generics } = &self.role else {
+                    commands::eval(scope, cmd)?;
+                    return Ok(Step::Done);
+                };
+
+                limits::charge(scope)?;
+                let value = r#yield::value(scope, value, item, generics)?;
+                return Ok(Step::Suspend(Suspend::Yield(value)));
+            }
+            Command::Await { value } if matches!(self.role, Role::Task { .. }) => {
+                limits::charge(scope)?;
+                let value = expr::eval(scope, value)?.as_value();
+
+                return match tasks::poll(scope, &value)? {
+                    Awaited::Ready(result) => {
+                        let value = result?;
+                        scope.context.lock().unwrap().push_prev_value(value);
+                        Ok(Step::Done)
+                    }
+                    Awaited::Pending(id) => {
+                        self.awaiting = Some(id);
+                        Ok(Step::Suspend(Suspend::Await(id)))
+                    }
+                };
+            }
+            Command::Builtin { name, args }
+                if &**name == ".time.sleep" && matches!(self.role, Role::Task { .. }) =>
+            {
+                limits::charge(scope)?;
+                let mut args_evaluated = Vec::with_capacity(args.len());
+                for arg in args {
+                    args_evaluated.push(expr::eval(scope, arg)?);
+                }
+
+                let duration = builtin::sleep_duration(scope, &args_evaluated)?;
+                return Ok(Step::Suspend(Suspend::Sleep(Instant::now() + duration)));
+            }
+            cmd => {
+                commands::eval(scope, cmd)?;
+                return Ok(Step::Done);
+            }
+        };
+
+        self.frames.push(Frame::new(inner, kind));
+        Ok(Step::Pushed)
+    }
+
+    /// Push the body of a called function, the others are called right away.
+    fn call(
+        &mut self,
+        name: &CommandExpression,
+        args: &[CommandExpression],
+    ) -> Result<Step, AmvmPropagate> {
+        let top = self.frames.len() - 1;
+        let frame = &mut self.frames[top];
+        let (callee, fun, args) = call::evaluate(&mut frame.scope, name, args)?;
+
+        let Some(ret) = frame_ret(&fun).cloned() else {
+            let value = call::call(&mut frame.scope, &callee, &fun, &args)?;
+            frame.scope.context.lock().unwrap().push_prev_value(value);
+            return Ok(Step::Done);
+        };
+
+        // Tail calls replace the frame of the function they return from,
+        // and keep its call site
+        let replaced = scope::is_tail_call(&frame.scope.body[frame.next - 1..])
+            .then(|| self.tail_frame())
+            .flatten();
+        let (caller, call_site) = match replaced {
+            Some(i) => {
+                let call_site = self.frames[i].scope.frame.as_ref();
+                (i - 1, call_site.and_then(|f| f.call_site.clone()))
+            }
+            None => {
+                let scope = &mut self.frames[top].scope;
+                check_depth(scope, self.calls)?;
+                (top, scope.meta.clone())
+            }
+        };
+
+        let scope = &mut self.frames[caller].scope;
+        let (inner, generics) = call::prepare(scope, &callee, call_site, &fun, &args)?;
+        if let Some(i) = replaced {
+            self.frames.truncate(i);
+            self.calls -= 1;
+        }
+
+        self.push_call(inner, ret, generics);
+        Ok(Step::Pushed)
+    }
+
+    /// Do the call that starts a task, its result is given when the
+    /// function doesn't run in a frame.
+    fn start(&mut self, start: Start) -> Option<AmvmResult> {
+        let Start {
+            mut caller,
+            name,
+            fun,
+            args,
+        } = start;
+        let Some(ret) = frame_ret(&fun).cloned() else {
+            return Some(call::call(&mut caller, &name, &fun, &args));
+        };
+
+        let call = check_depth(&mut caller, self.calls)
+            .and_then(|_| call::prepare(&mut caller, &name, None, &fun, &args));
+        match call {
+            Ok((inner, generics)) => {
+                self.push_call(inner, ret, generics);
+                None
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+
+    fn push_call(&mut self, inner: AmvmScope, ret: AmvmType, generics: types::AmvmGenerics) {
+        let kind = Kind::Call {
+            ret: Some(ret),
+            generics,
+        };
+
+        self.calls += 1;
+        self.frames.push(Frame::new(inner, kind));
+    }
+
+    /// Frame of the function being run, if a tail call can replace it.
+    fn tail_frame(&self) -> Option<usize> {
+        for (i, frame) in self.frames.iter().enumerate().rev() {
+            match frame.kind {
+                // Calls from `@try` are done in it, so their errors are caught
+                Kind::Try { .. } => return None,
+                // The first call gives its value to the caller of the machine
+                Kind::Call { .. } => return (i > 0).then_some(i),
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    /// The body of the top frame finished with `result`. Frames are popped
+    /// until one continues, the result of the machine is given when there
+    /// are none left.
+    fn complete(&mut self, mut result: AmvmResult) -> Option<AmvmResult> {
+        loop {
+            let last = self.frames.len() - 1;
+            let (parents, top) = self.frames.split_at_mut(last);
+            let Frame { scope, next, kind } = &mut top[0];
+            let parent = parents.last_mut().map(|frame| &mut frame.scope);
+
+            let done = match kind {
+                Kind::Block => Some(result),
+                Kind::Call { ret, generics } => {
+                    Some(call::finish(scope, result, ret.as_ref(), generics))
+                }
+                Kind::Loop { label } => match r#loop::flow(label, result) {
+                    Flow::Exit(result) => Some(result),
+                    Flow::Next => {
+                        let parent = parent.expect("Loops run in a frame");
+                        let inner = r#loop::run(parent, &scope.body);
+                        restart(scope, next, inner.map(Some))
+                    }
+                },
+                Kind::While {
+                    label,
+                    condition,
+                    is_do,
+                } => match r#loop::flow(label, result) {
+                    Flow::Exit(result) => Some(result),
+                    Flow::Next => {
+                        let parent = parent.expect("Loops run in a frame");
+                        let inner =
+                            r#while::repeats(scope, condition, *is_do).and_then(|repeats| {
+                                if !repeats {
+                                    return Ok(None);
+                                }
+
+                                r#while::run(parent, &scope.body, condition, *is_do)
+                            });
+                        restart(scope, next, inner)
+                    }
+                },
+                Kind::For { label, var, source } => match r#loop::flow(label, result) {
+                    Flow::Exit(result) => Some(result),
+                    Flow::Next => {
+                        let parent = parent.expect("Loops run in a frame");
+                        let inner = r#for::run(parent, &scope.body, var, source);
+                        restart(scope, next, inner)
+                    }
+                },
+                Kind::Try {
+                    catch,
+                    finally,
+                    phase,
+                } => {
+                    let parent = parent.expect("Blocks run in a frame");
+                    let caught = match phase {
+                        Phase::Body => r#try::catch_scope(parent, &result, catch),
+                        _ => None,
+                    };
+
+                    if let Some(inner) = caught {
+                        *scope = inner;
+                        *next = 0;
+                        *phase = Phase::Catch;
+                        None
+                    } else if let Phase::Finally(previous) = phase {
+                        let previous = std::mem::replace(previous, Ok(Value::Null));
+                        Some(r#try::finish(previous, result))
+                    } else if let Some(body) = finally {
+                        *scope = parent.create_sub(body.clone());
+                        *next = 0;
+                        *phase = Phase::Finally(result);
+                        None
+                    } else {
+                        Some(result.map(|_| Value::Null))
+                    }
+                }
+            };
+
+            // The frame continues
+            let done = done?;
+
+            let frame = self.frames.pop().expect("Finished frames are popped");
+            let is_call = matches!(frame.kind, Kind::Call { .. });
+            if is_call {
+                self.calls -= 1;
+            }
+
+            let Some(parent) = self.frames.last_mut() else {
+                return Some(done);
+            };
+
+            // The command that pushed the frame is done
+            parent.scope.meta = None;
+            match done {
+                Ok(value) => {
+                    if is_call {
+                        parent.scope.context.lock().unwrap().push_prev_value(value);
+                    }
+
+                    return None;
+                }
+                Err(err) => result = Err(err),
+            }
+        }
+    }
+}
+
+/// Return type of the functions whose body runs in a frame, others are
+/// called right away.
+fn frame_ret(fun: &ValueFun) -> Option<&AmvmType> {
+    match fun {
+        ValueFun::Const(_, _, ret, body, _) | ValueFun::Mutable(_, _, ret, body, ..)
+            if !r#yield::is_generator(body) =>
+        {
+            Some(ret)
+        }
+        _ => None,
+    }
+}
+
+/// Fail when one more call goes over the maximum call depth.
+fn check_depth(scope: &mut AmvmScope, calls: usize) -> Result<(), AmvmPropagate> {
+    if scope.usage.calls() + calls >= scope.options.max_call_depth {
+        return Err(AmvmPropagate::Err(scope.error("Stack overflow")));
+    }
+
+    Ok(())
+}
+
+/// Run the body of a loop again in `inner`, the loop is done when it's
+/// `None`.
+fn restart(
+    scope: &mut AmvmScope,
+    next: &mut usize,
+    inner: Result<Option<AmvmScope>, AmvmPropagate>,
+) -> Option<AmvmResult> {
+    match inner {
+        Ok(Some(inner)) => {
+            *scope = inner;
+            *next = 0;
+            None
+        }
+        Ok(None) => Some(Ok(Value::Null)),
+        Err(err) => Some(Err(err)),
+    }
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+/// Stack used by a call whose body nests a few blocks. Calls are evaluated
+/// recursively, with the commands and expressions of their bodies.
+const CALL_STACK_SIZE: usize = if cfg!(debug_assertions) {
+    128 * 1024
+} else {
+    32 * 1024
+};
+
+/// Stack used by the run besides the calls
+const BASE_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Limits of a [Runtime](super::Runtime), set with
+/// [Runtime::with_options](super::Runtime::with_options).
+///
+/// Exceeding the optional limits stops the run with
+/// [AmvmPropagate::Limit](super::AmvmPropagate::Limit), which the program
+/// can't catch.
+#[derive(Debug, Clone)]
+pub struct RuntimeOptions {
+    /// Calls that can be nested before failing with a stack overflow.
+    /// Tail calls don't count, they replace the function that does them.
+    ///
+    /// Calls use the stack of the thread that runs the program, which
+    /// should have [RuntimeOptions::stack_size] bytes for the deepest
+    /// calls to fit.
+    pub max_call_depth: usize,
+    /// Commands and expressions that can be evaluated
+    pub fuel: Option<u64>,
+    /// Wall-clock time the run can take
+    pub timeout: Option<Duration>,
+    /// Estimate in bytes of what the values of the live variables can use
+    pub max_heap: Option<usize>,
+    /// Variables that can be alive at once
+    pub max_variables: Option<usize>,
+}
+
+impl Default for RuntimeOptions {
+    fn default() -> Self {
+        Self {
+            max_call_depth: 1000,
+            fuel: None,
+            timeout: None,
+            max_heap: None,
+            max_variables: None,
+        }
+    }
+}
+
+impl RuntimeOptions {
+    /// Stack size of a thread where [RuntimeOptions::max_call_depth] nested
+    /// calls fit.
+    pub fn stack_size(&self) -> usize {
+        self.max_call_depth
+            .saturating_mul(CALL_STACK_SIZE)
+            .saturating_add(BASE_STACK_SIZE)
+    }
+}
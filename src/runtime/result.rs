@@ -1,10 +1,32 @@
-use crate::{runtime::AmvmError, tokens::Value};
+use std::rc::Rc;
+
+use crate::{
+    runtime::{AmvmError, AmvmLimit, AmvmVariable},
+    tokens::{AmvmMeta, Value, ValueFun},
+};
+
+/// Call whose result is returned, done by the caller of the function
+/// doing it once its frame is gone.
+#[derive(Debug, Clone)]
+pub struct AmvmTailCall {
+    pub name: Box<str>,
+    pub call_site: Option<Rc<AmvmMeta>>,
+    pub fun: ValueFun,
+    pub args: Vec<AmvmVariable>,
+}
 
 #[derive(Debug, Clone)]
 pub enum AmvmPropagate {
     Return(Value),
-    Break,
+    /// Carries the label of the loop to exit, if any
+    Break(Option<Box<str>>),
+    Continue(Option<Box<str>>),
     Err(AmvmError),
+    /// Value thrown by `@throw`, with where it was thrown
+    Throw(Box<Value>, Box<AmvmError>),
+    TailCall(Box<AmvmTailCall>),
+    /// A limit of the runtime options was exceeded, the run stops
+    Limit(AmvmLimit),
 }
 
 impl From<AmvmError> for AmvmPropagate {
@@ -19,7 +41,31 @@ impl AmvmPropagate {
     /// [`Break`]: AmvmPropagate::Break
     #[must_use]
     pub fn is_break(&self) -> bool {
-        matches!(self, Self::Break)
+        matches!(self, Self::Break(..))
+    }
+
+    /// Returns `true` if the amvm propagate is [`Continue`].
+    ///
+    /// [`Continue`]: AmvmPropagate::Continue
+    #[must_use]
+    pub fn is_continue(&self) -> bool {
+        matches!(self, Self::Continue(..))
+    }
+
+    /// Returns `true` if it's a [`Break`] that should be handled by the
+    /// loop with `label`.
+    ///
+    /// [`Break`]: AmvmPropagate::Break
+    pub fn breaks(&self, label: &Option<Box<str>>) -> bool {
+        matches!(self, Self::Break(target) if target.is_none() || target == label)
+    }
+
+    /// Returns `true` if it's a [`Continue`] that should be handled by the
+    /// loop with `label`.
+    ///
+    /// [`Continue`]: AmvmPropagate::Continue
+    pub fn continues(&self, label: &Option<Box<str>>) -> bool {
+        matches!(self, Self::Continue(target) if target.is_none() || target == label)
     }
 
     /// Returns `true` if the amvm propagate is [`Return`].
@@ -38,6 +84,36 @@ impl AmvmPropagate {
         matches!(self, Self::Err(..))
     }
 
+    /// Returns `true` if the amvm propagate is [`Throw`].
+    ///
+    /// [`Throw`]: AmvmPropagate::Throw
+    #[must_use]
+    pub fn is_throw(&self) -> bool {
+        matches!(self, Self::Throw(..))
+    }
+
+    /// Returns `true` if the amvm propagate is [`Limit`].
+    ///
+    /// [`Limit`]: AmvmPropagate::Limit
+    #[must_use]
+    pub fn is_limit(&self) -> bool {
+        matches!(self, Self::Limit(..))
+    }
+
+    /// Value that can be handled by `@catch`, runtime errors are
+    /// converted into an `#Error`.
+    pub fn to_catchable(&self) -> Option<Value> {
+        match self {
+            Self::Throw(value, _) => Some(Value::clone(value)),
+            Self::Err(err) => Some(err.to_value()),
+            Self::Return(_)
+            | Self::Break(_)
+            | Self::Continue(_)
+            | Self::TailCall(..)
+            | Self::Limit(_) => None,
+        }
+    }
+
     pub fn as_err(&self) -> Option<&AmvmError> {
         if let Self::Err(v) = self {
             Some(v)
@@ -1,20 +1,66 @@
 use crate::{
-    runtime::{commands, AmvmResult},
-    tokens::{AmvmScope, Command, Value},
+    runtime::{commands, AmvmPropagate, AmvmResult, AmvmTailCall},
+    tokens::{AmvmScope, Command, CommandExpression, Value},
 };
 
+/// `@call` whose result is returned right after it, `@ret _`.
+pub fn is_tail_call(body: &[Command]) -> bool {
+    let mut rest = body
+        .iter()
+        .filter(|cmd| !matches!(cmd, Command::Meta { .. }));
+
+    matches!(
+        (rest.next(), rest.next()),
+        (
+            Some(Command::Call { .. }),
+            Some(Command::Return {
+                value: CommandExpression::Prev
+            })
+        )
+    )
+}
+
+/// Give the call to the function being run, so it's done after its frame
+/// is dropped and tail recursion runs in constant stack space.
+fn tail_call(
+    scope: &mut AmvmScope,
+    name: &CommandExpression,
+    args: &[CommandExpression],
+) -> AmvmResult {
+    let (callee, fun, args) = commands::call::evaluate(scope, name, args)?;
+
+    Err(AmvmPropagate::TailCall(Box::new(AmvmTailCall {
+        name: callee,
+        call_site: scope.meta.clone(),
+        fun,
+        args,
+    })))
+}
+
+fn eval_commands(scope: &mut AmvmScope, body: &[Command]) -> AmvmResult {
+    // Only function bodies can return
+    let is_function = scope.depth > 0;
+
+    for (i, cmd) in body.iter().enumerate() {
+        match cmd {
+            Command::Call { name, args } if is_function && is_tail_call(&body[i..]) => {
+                return tail_call(scope, name, args);
+            }
+            cmd => commands::eval(scope, cmd)?,
+        };
+    }
+
+    Ok(Value::Null)
+}
+
 pub fn eval(scope: &mut AmvmScope, body: &[Command], use_same: bool) -> AmvmResult {
     if use_same {
-        for cmd in body {
-            commands::eval(scope, cmd)?;
-        }
+        eval_commands(scope, body)
     } else {
         let mut scope = scope.create_sub(body.to_vec());
 
-        for cmd in scope.body.clone().iter() {
-            commands::eval(&mut scope, cmd)?;
-        }
-    }
+        let body = scope.body.clone();
 
-    Ok(Value::Null)
+        eval_commands(&mut scope, &body)
+    }
 }
@@ -0,0 +1,253 @@
+//! Tasks started with `@spawn`, run by a single threaded executor.
+//!
+//! Each task is run by a machine that suspends it when it awaits a task that
+//! isn't finished or when it sleeps, the executor resumes it once it can
+//! continue. Code outside of tasks, and code called from a task that can't
+//! suspend it, drives the executor while it waits.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use std::{fmt, mem, thread};
+
+use crate::runtime::machine::{Machine, State, Suspend};
+use crate::runtime::{limits, AmvmPropagate, AmvmResult, AmvmVariable};
+use crate::tokens::{AmvmScope, AmvmType, Value, ValueFun, ValueObject};
+
+struct Task {
+    /// `None` while it's being run
+    machine: Option<Machine>,
+    result: Option<AmvmResult>,
+    /// Tasks awaiting this one
+    waiters: Vec<usize>,
+    /// Failed tasks are reported when nobody awaits them
+    is_awaited: bool,
+}
+
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Task>,
+    ready: VecDeque<usize>,
+    timers: BinaryHeap<Reverse<(Instant, usize)>>,
+    /// Task being run, `None` outside of tasks
+    current: Option<usize>,
+}
+
+impl fmt::Debug for Executor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Executor")
+            .field("tasks", &self.tasks.len())
+            .field("ready", &self.ready)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl Executor {
+    fn wake_timers(&mut self, now: Instant) {
+        while let Some(Reverse((at, id))) = self.timers.peek().copied() {
+            if at > now {
+                break;
+            }
+
+            self.timers.pop();
+            self.ready.push_back(id);
+        }
+    }
+
+    /// Result of a finished task, `None` while it runs.
+    fn take_result(&mut self, id: usize) -> Option<AmvmResult> {
+        let task = &mut self.tasks[id];
+        let result = task.result.clone()?;
+        task.is_awaited = true;
+
+        Some(result)
+    }
+}
+
+/// Instance of `#Task` for the task with this id
+fn task_value(id: usize) -> Value {
+    let mut fields = HashMap::new();
+    fields.insert(String::from("id"), Arc::new(RwLock::new(Value::Usize(id))));
+
+    Value::Object(ValueObject::Instance(
+        AmvmType::Named(Box::from("Task")),
+        fields,
+    ))
+}
+
+fn task_id(value: &Value) -> Option<usize> {
+    match value {
+        Value::Ref(var) => task_id(&var.read()),
+        Value::Object(ValueObject::Instance(AmvmType::Named(name), fields))
+            if &**name == "Task" =>
+        {
+            match fields.get("id").map(|id| id.read().unwrap().clone()) {
+                Some(Value::Usize(id)) => Some(id),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Start a task that calls `fun`, it runs the next time the executor
+/// is driven.
+pub fn spawn(scope: &AmvmScope, name: Box<str>, fun: ValueFun, args: Vec<AmvmVariable>) -> Value {
+    let mut executor = scope.tasks.borrow_mut();
+    let id = executor.tasks.len();
+
+    let machine = Machine::task(scope.create_sub(vec![]), name, fun, args);
+    executor.tasks.push(Task {
+        machine: Some(machine),
+        result: None,
+        waiters: vec![],
+        is_awaited: false,
+    });
+    executor.ready.push_back(id);
+
+    task_value(id)
+}
+
+/// Run the next ready task until it suspends or finishes. When no task is
+/// ready it waits for the next timer, but not after `until`.
+///
+/// Returns `false` when there is nothing left to do.
+fn step(scope: &AmvmScope, until: Option<Instant>) -> Result<bool, AmvmPropagate> {
+    let mut executor = scope.tasks.borrow_mut();
+    let now = Instant::now();
+    executor.wake_timers(now);
+
+    let Some(id) = executor.ready.pop_front() else {
+        let Some(Reverse((at, _))) = executor.timers.peek().copied() else {
+            return Ok(false);
+        };
+        drop(executor);
+
+        // Wake up in time for the deadline of the run
+        let at = [Some(at), until, scope.usage.deadline()]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(at);
+        thread::sleep(at.saturating_duration_since(now));
+        limits::check(scope)?;
+        return Ok(true);
+    };
+
+    let Some(mut machine) = executor.tasks[id].machine.take() else {
+        return Ok(true);
+    };
+    let previous = executor.current.replace(id);
+    drop(executor);
+
+    let state = machine.resume();
+
+    let mut executor = scope.tasks.borrow_mut();
+    executor.current = previous;
+    match state {
+        State::Suspended(Suspend::Await(awaited)) => {
+            executor.tasks[awaited].waiters.push(id);
+            executor.tasks[id].machine = Some(machine);
+        }
+        State::Suspended(Suspend::Sleep(until)) => {
+            executor.timers.push(Reverse((until, id)));
+            executor.tasks[id].machine = Some(machine);
+        }
+        State::Suspended(Suspend::Yield(_)) => unreachable!("Only generators yield"),
+        State::Finished(result) => {
+            let task = &mut executor.tasks[id];
+            task.result = Some(result);
+
+            let waiters = mem::take(&mut task.waiters);
+            executor.ready.extend(waiters);
+        }
+    }
+
+    Ok(true)
+}
+
+/// State of an awaited value
+pub enum Awaited {
+    /// Result of a finished task, any other value is given as is
+    Ready(AmvmResult),
+    /// Id of a task that didn't finish yet
+    Pending(usize),
+}
+
+/// Check if an awaited value is ready, without waiting.
+pub fn poll(scope: &mut AmvmScope, value: &Value) -> Result<Awaited, AmvmPropagate> {
+    let Some(id) = task_id(value) else {
+        return Ok(Awaited::Ready(Ok(value.clone())));
+    };
+    if id >= scope.tasks.borrow().tasks.len() {
+        return Err(AmvmPropagate::Err(scope.error("Unknown task")));
+    }
+
+    if let Some(result) = scope.tasks.borrow_mut().take_result(id) {
+        return Ok(Awaited::Ready(result));
+    }
+
+    if scope.tasks.borrow().current == Some(id) {
+        return Err(AmvmPropagate::Err(scope.error("A task can't await itself")));
+    }
+
+    Ok(Awaited::Pending(id))
+}
+
+/// Result of a task that finished, after awaiting it.
+pub fn result(scope: &AmvmScope, id: usize) -> AmvmResult {
+    let result = scope.tasks.borrow_mut().take_result(id);
+    result.expect("Awaited tasks are resumed once the other one finishes")
+}
+
+/// Wait for a task to finish and give its result, any other value is
+/// given as is. The executor runs the other tasks meanwhile.
+pub fn wait(scope: &mut AmvmScope, value: &Value) -> AmvmResult {
+    loop {
+        match poll(scope, value)? {
+            Awaited::Ready(result) => return result,
+            Awaited::Pending(_) => {
+                if !step(scope, None)? {
+                    return Err(AmvmPropagate::Err(
+                        scope.error("Awaiting a task that never finishes"),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Wait without blocking other tasks, the executor runs them meanwhile.
+pub fn sleep(scope: &mut AmvmScope, duration: Duration) -> Result<(), AmvmPropagate> {
+    let until = Instant::now() + duration;
+
+    while Instant::now() < until {
+        if !step(scope, Some(until))? {
+            let at = scope.usage.deadline().map_or(until, |at| at.min(until));
+            thread::sleep(at.saturating_duration_since(Instant::now()));
+            limits::check(scope)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Run every task until they finish. The first error of a task that was
+/// never awaited is returned.
+pub fn run_all(scope: &mut AmvmScope) -> AmvmResult {
+    while step(scope, None)? {}
+
+    let executor = scope.tasks.borrow();
+    let failed = executor
+        .tasks
+        .iter()
+        .filter(|task| !task.is_awaited)
+        .find_map(|task| task.result.clone()?.err());
+
+    match failed {
+        Some(err) => Err(err),
+        None => Ok(Value::Null),
+    }
+}
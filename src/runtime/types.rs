@@ -0,0 +1,335 @@
+use std::collections::HashMap;
+
+use crate::tokens::{
+    AmvmPrimitiveType, AmvmScope, AmvmType, AmvmTypeDefinition, Value, ValueFun, ValueObject,
+};
+
+/// Generic parameters in scope. A parameter without value is not bound yet
+/// and will be inferred from the first value checked against it.
+pub type AmvmGenerics = HashMap<Box<str>, Option<AmvmType>>;
+
+pub fn generics_from_decl(decl: &[(Box<str>, Option<AmvmType>)]) -> AmvmGenerics {
+    decl.iter()
+        .map(|(name, default)| (name.clone(), default.clone()))
+        .collect()
+}
+
+pub fn type_of(value: &Value) -> AmvmType {
+    match value {
+        Value::Null => AmvmType::Named(Box::from("null")),
+        Value::Bool(_) => AmvmType::Primitive(AmvmPrimitiveType::Bool),
+        Value::Char(_) => AmvmType::Named(Box::from("char")),
+        Value::I16(_) => AmvmType::Named(Box::from("i16")),
+        Value::F32(_) => AmvmType::Named(Box::from("f32")),
+        Value::String(_) => AmvmType::Primitive(AmvmPrimitiveType::String),
+        Value::U8(_) => AmvmType::Primitive(AmvmPrimitiveType::U8),
+        Value::Usize(_) => AmvmType::Named(Box::from("usize")),
+
+        Value::Fun(fun) => match fun {
+            ValueFun::Native(args, ret, _)
+            | ValueFun::Const(_, args, ret, ..)
+            | ValueFun::Mutable(_, args, ret, ..) => AmvmType::Fun(
+                args.iter().map(|arg| arg.2.clone()).collect(),
+                Box::new(ret.clone()),
+            ),
+        },
+        Value::Object(ValueObject::Instance(ty, _) | ValueObject::Variant(ty, ..)) => ty.clone(),
+        Value::Object(_) => AmvmType::Anonymous,
+        Value::Ref(var) => type_of(&var.read()),
+        Value::List(values) => AmvmType::List(Box::new(
+            values.first().map_or(AmvmType::Anonymous, type_of),
+        )),
+        Value::Map(map) => {
+            let (key, value) = map.iter().next().map_or(
+                (AmvmType::Anonymous, AmvmType::Anonymous),
+                |(key, value)| (type_of(&key.to_value()), type_of(value)),
+            );
+
+            AmvmType::Generic(Box::from("Map"), vec![key, value])
+        }
+        Value::Tuple(values) => AmvmType::Tuple(values.iter().map(type_of).collect()),
+    }
+}
+
+/// Check if `value` can be stored in a slot of type `ty`. Unbound generics
+/// get bound to the type of the value.
+pub fn check(scope: &AmvmScope, value: &Value, ty: &AmvmType, generics: &mut AmvmGenerics) -> bool {
+    if let Value::Ref(var) = value {
+        return check(scope, &var.read(), ty, generics);
+    }
+
+    match ty {
+        AmvmType::Anonymous => true,
+        AmvmType::Primitive(primitive) => matches!(
+            (primitive, value),
+            (AmvmPrimitiveType::Bool, Value::Bool(_))
+                | (AmvmPrimitiveType::String, Value::String(_))
+                | (AmvmPrimitiveType::U8, Value::U8(_))
+        ),
+
+        AmvmType::Union(a, b) => {
+            check(scope, value, a, generics) || check(scope, value, b, generics)
+        }
+        AmvmType::Nullable(ty) => matches!(value, Value::Null) || check(scope, value, ty, generics),
+        AmvmType::List(ty) => match value {
+            Value::List(values) => values.iter().all(|value| check(scope, value, ty, generics)),
+            _ => false,
+        },
+        AmvmType::Tuple(types) => match value {
+            Value::Tuple(values) => {
+                values.len() == types.len()
+                    && values
+                        .iter()
+                        .zip(types)
+                        .all(|(value, ty)| check(scope, value, ty, generics))
+            }
+            _ => false,
+        },
+
+        AmvmType::Fun(args, _) => match value {
+            Value::Fun(
+                ValueFun::Native(fun_args, ..)
+                | ValueFun::Const(_, fun_args, ..)
+                | ValueFun::Mutable(_, fun_args, ..),
+            ) => fun_args.len() == args.len(),
+            _ => false,
+        },
+
+        AmvmType::Named(name) => {
+            if let Some(bound) = generics.get(name) {
+                return match bound.clone() {
+                    Some(bound) => check(scope, value, &bound, generics),
+                    None => {
+                        generics.insert(name.clone(), Some(type_of(value)));
+                        true
+                    }
+                };
+            }
+
+            match (name.as_ref(), value) {
+                ("null", Value::Null)
+                | ("bool", Value::Bool(_))
+                | ("char", Value::Char(_))
+                | ("i16", Value::I16(_))
+                | ("f32", Value::F32(_))
+                | ("fn", Value::Fun(_))
+                | ("Map", Value::Map(_))
+                | ("string", Value::String(_))
+                | ("u8", Value::U8(_))
+                | ("usize", Value::Usize(_)) => true,
+                (
+                    "null" | "bool" | "char" | "i16" | "f32" | "fn" | "Map" | "string" | "u8"
+                    | "usize",
+                    _,
+                ) => false,
+
+                _ => {
+                    let alias = scope.context.lock().unwrap().get_alias(name);
+                    if let Some(alias) = alias {
+                        return check(scope, value, &alias, generics);
+                    }
+
+                    let is_struct = scope.context.lock().unwrap().get_struct(name).is_some();
+
+                    // Types that aren't declared can't be verified
+                    !is_struct || instance_of(scope, value, name)
+                }
+            }
+        }
+        AmvmType::Generic(name, args) if name.as_ref() == "Map" => {
+            let (Value::Map(map), [key_ty, value_ty]) = (value, args.as_slice()) else {
+                return false;
+            };
+
+            map.iter().all(|(key, value)| {
+                check(scope, &key.to_value(), key_ty, generics)
+                    && check(scope, value, value_ty, generics)
+            })
+        }
+        AmvmType::Generic(name, args) => {
+            if !instance_of(scope, value, name) {
+                return false;
+            }
+
+            match value {
+                Value::Object(ValueObject::Instance(AmvmType::Generic(_, value_args), _)) => args
+                    .iter()
+                    .zip(value_args)
+                    .all(|(arg, value_arg)| unify(arg, value_arg, generics)),
+                _ => true,
+            }
+        }
+    }
+}
+
+/// Replace type aliases with the type they refer to, the inner types
+/// are resolved when they are checked.
+pub fn resolve(scope: &AmvmScope, ty: &AmvmType) -> AmvmType {
+    let alias = match ty {
+        AmvmType::Named(name) => scope.context.lock().unwrap().get_alias(name),
+        _ => None,
+    };
+
+    match alias {
+        Some(alias) => resolve(scope, &alias),
+        None => ty.clone(),
+    }
+}
+
+/// Compare a type argument, binding it if it's a generic parameter without
+/// type. Arguments that weren't inferred can't be verified.
+fn unify(ty: &AmvmType, value_ty: &AmvmType, generics: &mut AmvmGenerics) -> bool {
+    if *value_ty == AmvmType::Anonymous {
+        return true;
+    }
+
+    if let AmvmType::Named(name) = ty {
+        if let Some(bound) = generics.get_mut(name) {
+            return match bound {
+                Some(bound) => bound == value_ty,
+                None => {
+                    *bound = Some(value_ty.clone());
+                    true
+                }
+            };
+        }
+    }
+
+    ty == value_ty
+}
+
+fn instance_of(scope: &AmvmScope, value: &Value, name: &str) -> bool {
+    let Value::Object(ValueObject::Instance(ty, _) | ValueObject::Variant(ty, ..)) = value else {
+        return false;
+    };
+
+    ty.name()
+        .is_some_and(|ty| ty == name || inherits(scope, ty, name))
+}
+
+/// Check if the declaration `name` inherits from `parent`, directly or not.
+pub fn inherits(scope: &AmvmScope, name: &str, parent: &str) -> bool {
+    let decl = scope.context.lock().unwrap().get_struct(name);
+    let Some(AmvmTypeDefinition::Inheritance(parents, _)) = decl else {
+        return false;
+    };
+
+    parents
+        .iter()
+        .filter_map(AmvmType::name)
+        .any(|name| name == parent || inherits(scope, name, parent))
+}
+
+/// Replace bound generic parameters inside `ty`
+pub fn substitute(ty: &AmvmType, generics: &AmvmGenerics) -> AmvmType {
+    match ty {
+        AmvmType::Named(name) => match generics.get(name) {
+            Some(Some(bound)) => bound.clone(),
+            _ => ty.clone(),
+        },
+        AmvmType::Generic(name, args) => AmvmType::Generic(
+            name.clone(),
+            args.iter().map(|arg| substitute(arg, generics)).collect(),
+        ),
+        AmvmType::List(ty) => AmvmType::List(Box::new(substitute(ty, generics))),
+        AmvmType::Nullable(ty) => AmvmType::Nullable(Box::new(substitute(ty, generics))),
+        AmvmType::Tuple(types) => {
+            AmvmType::Tuple(types.iter().map(|ty| substitute(ty, generics)).collect())
+        }
+        AmvmType::Union(a, b) => AmvmType::Union(
+            Box::new(substitute(a, generics)),
+            Box::new(substitute(b, generics)),
+        ),
+        AmvmType::Fun(args, ret) => AmvmType::Fun(
+            args.iter().map(|arg| substitute(arg, generics)).collect(),
+            Box::new(substitute(ret, generics)),
+        ),
+        AmvmType::Anonymous | AmvmType::Primitive(_) => ty.clone(),
+    }
+}
+
+/// Fields of a declaration, including the inherited ones.
+pub fn fields_of(scope: &AmvmScope, decl: &AmvmTypeDefinition) -> Vec<(Box<str>, AmvmType)> {
+    match decl {
+        AmvmTypeDefinition::Struct { fields, .. } => fields.clone(),
+        AmvmTypeDefinition::Enum { .. } => vec![],
+        AmvmTypeDefinition::Inheritance(parents, decl) => {
+            let mut fields = inherited_fields(scope, parents);
+
+            for field in fields_of(scope, decl) {
+                match fields.iter_mut().find(|f| f.0 == field.0) {
+                    Some(inherited) => *inherited = field,
+                    None => fields.push(field),
+                }
+            }
+
+            fields
+        }
+    }
+}
+
+/// Fields required by the parents of a declaration, with their generics applied.
+pub fn inherited_fields(scope: &AmvmScope, parents: &[AmvmType]) -> Vec<(Box<str>, AmvmType)> {
+    let mut fields: Vec<(Box<str>, AmvmType)> = vec![];
+
+    for parent in parents {
+        let Some(decl) = parent
+            .name()
+            .and_then(|name| scope.context.lock().unwrap().get_struct(name))
+        else {
+            continue;
+        };
+
+        let mut generics = AmvmGenerics::new();
+        if let (Some(decl_generics), AmvmType::Generic(_, args)) = (decl.generics(), parent) {
+            for ((name, _), arg) in decl_generics.iter().zip(args) {
+                generics.insert(name.clone(), Some(arg.clone()));
+            }
+        }
+
+        for (name, ty) in fields_of(scope, &decl) {
+            if !fields.iter().any(|f| f.0 == name) {
+                fields.push((name, substitute(&ty, &generics)));
+            }
+        }
+    }
+
+    fields
+}
+
+/// Check if a field type declared by a struct satisfies the inherited one.
+pub fn conforms(own: &AmvmType, inherited: &AmvmType) -> bool {
+    match (own, inherited) {
+        (_, AmvmType::Anonymous) => true,
+        (AmvmType::Fun(a, _), AmvmType::Fun(b, _)) => a.len() == b.len(),
+        (AmvmType::Named(name), AmvmType::Fun(..)) => name.as_ref() == "fn",
+        (own, inherited) => own == inherited,
+    }
+}
+
+/// Generic parameters of a declaration applied with the arguments of `ty`
+pub fn apply_generics(
+    scope: &mut AmvmScope,
+    decl: &AmvmTypeDefinition,
+    ty: &AmvmType,
+) -> Result<AmvmGenerics, crate::runtime::AmvmError> {
+    let Some(generics) = decl.generics() else {
+        return Ok(AmvmGenerics::new());
+    };
+
+    let args: &[AmvmType] = match ty {
+        AmvmType::Generic(_, args) => args,
+        _ => &[],
+    };
+
+    if args.len() > generics.len() {
+        return Err(scope.error("Wrong number of generic arguments"));
+    }
+
+    let mut applied = generics_from_decl(generics);
+    for ((name, _), arg) in generics.iter().zip(args) {
+        applied.insert(name.clone(), Some(arg.clone()));
+    }
+
+    Ok(applied)
+}
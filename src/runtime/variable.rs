@@ -57,6 +57,18 @@ impl AmvmVariable {
         }
     }
 
+    /// Check if both are the same variable, not only equal values.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Const(a), Self::Const(b)) => Arc::ptr_eq(a, b),
+            (
+                Self::Mut(a) | Self::Let(a) | Self::Var(a),
+                Self::Mut(b) | Self::Let(b) | Self::Var(b),
+            ) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
     pub fn is_mutable(&self) -> bool {
         match self {
             Self::Const(_) | Self::Let(_) => false,
@@ -71,6 +83,14 @@ impl AmvmVariable {
         }
     }
 
+    /// Run `f` with the value, without copying it like [Self::read].
+    pub fn with<T>(&self, f: impl FnOnce(&Value) -> T) -> T {
+        match self {
+            Self::Const(v) => f(v),
+            Self::Mut(v) | Self::Let(v) | Self::Var(v) => f(&v.read().unwrap()),
+        }
+    }
+
     pub fn write(&self) -> Option<RwLockWriteGuard<'_, Value>> {
         match self {
             Self::Const(_) => None,
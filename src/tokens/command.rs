@@ -5,8 +5,8 @@ use crate::{
     create_bytes,
     parser::{self, Parser, ParserResult},
     tokens::{
-        AmvmType, CommandExpression, Value, VariableKind, COMMAND_SEPARATOR, VAR_CONST, VAR_LET,
-        VAR_MUT, VAR_VAR,
+        AmvmType, AmvmTypeDefinition, CommandExpression, CommandPattern, EnumVariant, Value,
+        VariableKind, COMMAND_SEPARATOR, VAR_CONST, VAR_LET, VAR_MUT, VAR_VAR,
     },
     Compilable,
 };
@@ -27,7 +27,18 @@ create_bytes! {0x50;
     CMD_PUTS,
     CMD_RET,
     CMD_SCOPE,
-    CMD_STRUCT
+    CMD_STRUCT,
+    CMD_MATCH,
+    CMD_ENUM,
+    CMD_STATIC,
+    CMD_TYPE,
+    CMD_THROW,
+    CMD_TRY,
+    CMD_CONTINUE,
+    CMD_WHILE,
+    CMD_YIELD,
+    CMD_SPAWN,
+    CMD_AWAIT
 }
 
 #[derive(Debug, Clone)]
@@ -37,7 +48,15 @@ pub enum Command {
         value: CommandExpression,
     },
 
-    Break,
+    /// Waits for a task to finish, its result is the previous value
+    Await {
+        value: CommandExpression,
+    },
+
+    /// Exits the innermost loop, or the one with the label
+    Break {
+        label: Option<Box<str>>,
+    },
 
     Builtin {
         name: Box<str>,
@@ -49,6 +68,10 @@ pub enum Command {
         args: Vec<CommandExpression>,
     },
 
+    Continue {
+        label: Option<Box<str>>,
+    },
+
     Conditional {
         condition: CommandExpression,
         body: Vec<Command>,
@@ -61,7 +84,13 @@ pub enum Command {
         value: CommandExpression,
     },
 
+    Enum {
+        name: Box<str>,
+        variants: Vec<EnumVariant>,
+    },
+
     For {
+        label: Option<Box<str>>,
         var: Box<str>,
         iterator: CommandExpression,
         body: Vec<Command>,
@@ -69,11 +98,19 @@ pub enum Command {
 
     Function {
         name: Box<str>,
+        /// Mutable functions keep their static variables between calls
+        is_mutable: bool,
+        generics: Vec<(Box<str>, Option<AmvmType>)>,
         args: Vec<(Box<str>, VariableKind, AmvmType)>,
         ret: AmvmType,
         body: Vec<Command>,
     },
 
+    Match {
+        value: CommandExpression,
+        arms: Vec<(CommandPattern, Vec<Command>)>,
+    },
+
     Meta {
         pos: (u16, u16),
         code: Box<str>,
@@ -82,6 +119,7 @@ pub enum Command {
     MetaFile(Box<str>),
 
     Loop {
+        label: Option<Box<str>>,
         body: Vec<Command>,
     },
 
@@ -101,10 +139,52 @@ pub enum Command {
         body: Vec<Command>,
     },
 
+    /// Starts a task that calls the function, the task is the previous value
+    Spawn {
+        name: CommandExpression,
+        args: Vec<CommandExpression>,
+    },
+
+    Static {
+        name: Box<str>,
+        value: CommandExpression,
+    },
+
     Struct {
         name: Box<str>,
+        generics: Vec<(Box<str>, Option<AmvmType>)>,
+        inherits: Vec<AmvmType>,
         body: Vec<(Box<str>, AmvmType)>,
     },
+
+    Throw {
+        value: CommandExpression,
+    },
+
+    Try {
+        body: Vec<Command>,
+        /// Variable that holds the error, and the body that handles it
+        catch: Option<(Box<str>, Vec<Command>)>,
+        finally: Option<Vec<Command>>,
+    },
+
+    Type {
+        name: Box<str>,
+        ty: AmvmType,
+    },
+
+    While {
+        label: Option<Box<str>>,
+        condition: CommandExpression,
+        body: Vec<Command>,
+        /// `@do { ... } @while`, the body runs before the first check
+        is_do: bool,
+    },
+
+    /// Suspends the generator it's in, giving the value to `next`
+    Yield {
+        value: CommandExpression,
+    },
 }
 
 impl Command {
@@ -172,7 +252,22 @@ impl Command {
         ))
     }
 
-    fn visit_scope(parser: Parser<'_>) -> ParserResult<'_, Vec<Self>> {
+    /// Labels are stored as strings, an empty one means no label.
+    fn visit_label(parser: Parser<'_>) -> ParserResult<'_, Option<Box<str>>> {
+        let (parser, label) = Value::visit_string(parser)?;
+        let label = (!label.is_empty()).then(|| Box::from(label));
+
+        Ok((parser, label))
+    }
+
+    fn compile_label(buffer: String, label: &Option<Box<str>>) -> CompileResult {
+        label
+            .as_deref()
+            .unwrap_or_default()
+            .compile_bytecode(buffer)
+    }
+
+    pub fn visit_scope(parser: Parser<'_>) -> ParserResult<'_, Vec<Self>> {
         Value::visit_slice(parser, Command::visit)
     }
 
@@ -183,7 +278,15 @@ impl Command {
         let (parser, value) = match b {
             _ if b == CMD_ASGN_VAR => Self::visit_asgn(parser)?,
 
-            _ if b == CMD_BREAK => (parser, Command::Break),
+            _ if b == CMD_BREAK => {
+                let (parser, label) = Self::visit_label(parser)?;
+                (parser, Command::Break { label })
+            }
+
+            _ if b == CMD_CONTINUE => {
+                let (parser, label) = Self::visit_label(parser)?;
+                (parser, Command::Continue { label })
+            }
 
             _ if b == CMD_BUILTIN => {
                 let (parser, name) = Value::visit_string(parser)?;
@@ -230,6 +333,9 @@ impl Command {
 
             _ if b == CMD_FN => {
                 let (parser, name) = Value::visit_string(parser)?;
+                let (parser, is_mutable) = parser::anychar(parser)
+                    .map_err(parser.nom_err_with_context("Expected function kind"))?;
+                let (parser, generics) = AmvmTypeDefinition::visit_generics(parser)?;
                 let (parser, args) = Value::visit_slice(parser, |parser| {
                     let (parser, name) = Value::visit_string(parser)?;
                     let (parser, kind) = Self::visit_kind(parser)?;
@@ -244,6 +350,8 @@ impl Command {
                     parser,
                     Command::Function {
                         name: name.into(),
+                        is_mutable: is_mutable == '\x01',
+                        generics,
                         args,
                         ret,
                         body,
@@ -252,6 +360,7 @@ impl Command {
             }
 
             _ if b == CMD_FOR => {
+                let (parser, label) = Self::visit_label(parser)?;
                 let (parser, var) = Value::visit_string(parser)?;
                 let (parser, iterator) = CommandExpression::visit(parser)?;
                 let (parser, body) = Self::visit_scope(parser)?;
@@ -259,6 +368,7 @@ impl Command {
                 (
                     parser,
                     Command::For {
+                        label,
                         var: var.into(),
                         iterator,
                         body,
@@ -267,8 +377,44 @@ impl Command {
             }
 
             _ if b == CMD_LOOP => {
+                let (parser, label) = Self::visit_label(parser)?;
                 let (parser, body) = Self::visit_scope(parser)?;
-                (parser, Command::Loop { body })
+                (parser, Command::Loop { label, body })
+            }
+
+            _ if b == CMD_ENUM => {
+                let (parser, name) = Value::visit_string(parser)?;
+                let (parser, variants) = Value::visit_slice(parser, |parser| {
+                    let (parser, variant) = Value::visit_string(parser)?;
+                    let (parser, fields) = Value::visit_slice(parser, |parser| {
+                        let (parser, name) = Value::visit_string(parser)?;
+                        let (parser, ty) = AmvmType::visit(parser)?;
+
+                        Ok((parser, (Box::from(name), ty)))
+                    })?;
+
+                    Ok((parser, (Box::from(variant), fields)))
+                })?;
+
+                (
+                    parser,
+                    Command::Enum {
+                        name: Box::from(name),
+                        variants,
+                    },
+                )
+            }
+
+            _ if b == CMD_MATCH => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+                let (parser, arms) = Value::visit_slice(parser, |parser| {
+                    let (parser, pattern) = CommandPattern::visit(parser)?;
+                    let (parser, body) = Self::visit_scope(parser)?;
+
+                    Ok((parser, (pattern, body)))
+                })?;
+
+                (parser, Command::Match { value, arms })
             }
 
             _ if b == CMD_META => {
@@ -307,6 +453,101 @@ impl Command {
                 (parser, Command::Puts { value })
             }
 
+            _ if b == CMD_STATIC => {
+                let (parser, name) = Value::visit_string(parser)?;
+                let (parser, value) = CommandExpression::visit(parser)?;
+
+                (
+                    parser,
+                    Command::Static {
+                        name: name.into(),
+                        value,
+                    },
+                )
+            }
+
+            _ if b == CMD_THROW => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+                (parser, Command::Throw { value })
+            }
+
+            _ if b == CMD_TRY => {
+                let (parser, body) = Self::visit_scope(parser)?;
+
+                let (parser, has_catch) = parser::anychar(parser)?;
+                let (parser, catch) = if has_catch == '\x01' {
+                    let (parser, name) = Value::visit_string(parser)?;
+                    let (parser, body) = Self::visit_scope(parser)?;
+                    (parser, Some((Box::from(name), body)))
+                } else {
+                    (parser, None)
+                };
+
+                let (parser, has_finally) = parser::anychar(parser)?;
+                let (parser, finally) = if has_finally == '\x01' {
+                    let (parser, body) = Self::visit_scope(parser)?;
+                    (parser, Some(body))
+                } else {
+                    (parser, None)
+                };
+
+                (
+                    parser,
+                    Command::Try {
+                        body,
+                        catch,
+                        finally,
+                    },
+                )
+            }
+
+            _ if b == CMD_WHILE => {
+                let (parser, label) = Self::visit_label(parser)?;
+                let (parser, is_do) = parser::anychar(parser)?;
+                let (parser, condition) = CommandExpression::visit(parser)?;
+                let (parser, body) = Self::visit_scope(parser)?;
+
+                (
+                    parser,
+                    Command::While {
+                        label,
+                        condition,
+                        body,
+                        is_do: is_do == '\x01',
+                    },
+                )
+            }
+
+            _ if b == CMD_YIELD => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+                (parser, Command::Yield { value })
+            }
+
+            _ if b == CMD_SPAWN => {
+                let (parser, name) = CommandExpression::visit(parser)?;
+                let (parser, args) = Value::visit_slice(parser, CommandExpression::visit)?;
+
+                (parser, Command::Spawn { name, args })
+            }
+
+            _ if b == CMD_AWAIT => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+                (parser, Command::Await { value })
+            }
+
+            _ if b == CMD_TYPE => {
+                let (parser, name) = Value::visit_string(parser)?;
+                let (parser, ty) = AmvmType::visit(parser)?;
+
+                (
+                    parser,
+                    Command::Type {
+                        name: name.into(),
+                        ty,
+                    },
+                )
+            }
+
             _ if b == CMD_RET => {
                 let (parser, value) = CommandExpression::visit(parser)?;
                 (parser, Command::Return { value })
@@ -319,6 +560,8 @@ impl Command {
 
             _ if b == CMD_STRUCT => {
                 let (parser, name) = Value::visit_string(parser)?;
+                let (parser, generics) = AmvmTypeDefinition::visit_generics(parser)?;
+                let (parser, inherits) = Value::visit_slice(parser, AmvmType::visit)?;
                 let (parser, body) = Value::visit_slice(parser, |parser| {
                     let (parser, name) = Value::visit_string(parser)?;
                     let (parser, ty) = AmvmType::visit(parser)?;
@@ -327,7 +570,15 @@ impl Command {
                 })?;
 
                 let name = Box::from(name);
-                (parser, Command::Struct { name, body })
+                (
+                    parser,
+                    Command::Struct {
+                        name,
+                        generics,
+                        inherits,
+                        body,
+                    },
+                )
             }
 
             _ => {
@@ -349,7 +600,14 @@ impl Compilable for Command {
                 buffer = name.compile_bytecode(buffer)?;
                 buffer = value.compile_bytecode(buffer)?;
             }
-            Self::Break => _ = buffer.write_char(CMD_BREAK),
+            Self::Break { label } => {
+                _ = buffer.write_char(CMD_BREAK);
+                buffer = Self::compile_label(buffer, label)?;
+            }
+            Self::Continue { label } => {
+                _ = buffer.write_char(CMD_CONTINUE);
+                buffer = Self::compile_label(buffer, label)?;
+            }
             Self::Builtin { name, args } => {
                 _ = buffer.write_char(CMD_BUILTIN);
                 buffer = name.compile_bytecode(buffer)?;
@@ -381,23 +639,29 @@ impl Compilable for Command {
                 buffer = value.compile_bytecode(buffer)?;
             }
             Self::For {
+                label,
                 var,
                 iterator,
                 body,
             } => {
                 _ = buffer.write_char(CMD_FOR);
+                buffer = Self::compile_label(buffer, label)?;
                 buffer = var.compile_bytecode(buffer)?;
                 buffer = iterator.compile_bytecode(buffer)?;
                 buffer = body.compile_bytecode(buffer)?;
             }
             Self::Function {
                 name,
+                is_mutable,
+                generics,
                 args,
                 ret,
                 body,
             } => {
                 _ = buffer.write_char(CMD_FN);
                 buffer = name.compile_bytecode(buffer)?;
+                _ = buffer.write_char(if *is_mutable { '\x01' } else { '\x00' });
+                buffer = compile_generics(buffer, generics)?;
                 buffer = (
                     args,
                     |mut buffer: String,
@@ -413,10 +677,37 @@ impl Compilable for Command {
                 buffer = ret.compile_bytecode(buffer)?;
                 buffer = body.compile_bytecode(buffer)?;
             }
-            Self::Loop { body } => {
+            Self::Loop { label, body } => {
                 _ = buffer.write_char(CMD_LOOP);
+                buffer = Self::compile_label(buffer, label)?;
                 buffer = body.compile_bytecode(buffer)?;
             }
+            Self::Enum { name, variants } => {
+                _ = buffer.write_char(CMD_ENUM);
+                buffer = name.compile_bytecode(buffer)?;
+                buffer = (
+                    variants,
+                    |mut buffer: String, variant: &EnumVariant| -> CompileResult {
+                        buffer = variant.0.compile_bytecode(buffer)?;
+                        buffer = variant.1.compile_bytecode(buffer)?;
+                        Ok(buffer)
+                    },
+                )
+                    .compile_bytecode(buffer)?;
+            }
+            Self::Match { value, arms } => {
+                _ = buffer.write_char(CMD_MATCH);
+                buffer = value.compile_bytecode(buffer)?;
+                buffer = (
+                    arms,
+                    |mut buffer: String, arm: &(CommandPattern, Vec<Command>)| -> CompileResult {
+                        buffer = arm.0.compile_bytecode(buffer)?;
+                        buffer = arm.1.compile_bytecode(buffer)?;
+                        Ok(buffer)
+                    },
+                )
+                    .compile_bytecode(buffer)?;
+            }
             Self::Meta { pos, code } => {
                 _ = buffer.write_char(CMD_META);
                 buffer = pos.0.compile_bytecode(buffer)?;
@@ -435,6 +726,66 @@ impl Compilable for Command {
                 _ = buffer.write_char(CMD_PUTS);
                 buffer = value.compile_bytecode(buffer)?;
             }
+            Self::Static { name, value } => {
+                _ = buffer.write_char(CMD_STATIC);
+                buffer = name.compile_bytecode(buffer)?;
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Throw { value } => {
+                _ = buffer.write_char(CMD_THROW);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                _ = buffer.write_char(CMD_TRY);
+                buffer = body.compile_bytecode(buffer)?;
+                if let Some((name, body)) = catch {
+                    _ = buffer.write_char('\x01');
+                    buffer = name.compile_bytecode(buffer)?;
+                    buffer = body.compile_bytecode(buffer)?;
+                } else {
+                    _ = buffer.write_char('\x00');
+                }
+                if let Some(finally) = finally {
+                    _ = buffer.write_char('\x01');
+                    buffer = finally.compile_bytecode(buffer)?;
+                } else {
+                    _ = buffer.write_char('\x00');
+                }
+            }
+            Self::While {
+                label,
+                condition,
+                body,
+                is_do,
+            } => {
+                _ = buffer.write_char(CMD_WHILE);
+                buffer = Self::compile_label(buffer, label)?;
+                _ = buffer.write_char(if *is_do { '\x01' } else { '\x00' });
+                buffer = condition.compile_bytecode(buffer)?;
+                buffer = body.compile_bytecode(buffer)?;
+            }
+            Self::Yield { value } => {
+                _ = buffer.write_char(CMD_YIELD);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Spawn { name, args } => {
+                _ = buffer.write_char(CMD_SPAWN);
+                buffer = name.compile_bytecode(buffer)?;
+                buffer = Value::compile_slice(buffer, args)?;
+            }
+            Self::Await { value } => {
+                _ = buffer.write_char(CMD_AWAIT);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Type { name, ty } => {
+                _ = buffer.write_char(CMD_TYPE);
+                buffer = name.compile_bytecode(buffer)?;
+                buffer = ty.compile_bytecode(buffer)?;
+            }
             Self::Return { value } => {
                 _ = buffer.write_char(CMD_RET);
                 buffer = value.compile_bytecode(buffer)?;
@@ -443,9 +794,16 @@ impl Compilable for Command {
                 _ = buffer.write_char(CMD_SCOPE);
                 buffer = body.compile_bytecode(buffer)?;
             }
-            Self::Struct { name, body } => {
+            Self::Struct {
+                name,
+                generics,
+                inherits,
+                body,
+            } => {
                 _ = buffer.write_char(CMD_STRUCT);
                 buffer = name.compile_bytecode(buffer)?;
+                buffer = compile_generics(buffer, generics)?;
+                buffer = Value::compile_slice(buffer, inherits)?;
                 buffer = body.compile_bytecode(buffer)?;
             }
         }
@@ -474,6 +832,50 @@ pub fn fmt_body(f: &mut fmt::Formatter, body: &[Command]) -> fmt::Result {
     Ok(())
 }
 
+fn fmt_label(label: &Option<Box<str>>) -> String {
+    label
+        .as_ref()
+        .map_or_else(String::new, |label| format!(" '{label}"))
+}
+
+fn compile_generics(
+    mut buffer: String,
+    generics: &Vec<(Box<str>, Option<AmvmType>)>,
+) -> CompileResult {
+    buffer = (
+        generics,
+        |mut buffer: String, generic: &(Box<str>, Option<AmvmType>)| -> CompileResult {
+            buffer = generic.0.compile_bytecode(buffer)?;
+            if let Some(default) = &generic.1 {
+                buffer = default.compile_bytecode(buffer)?;
+            } else {
+                _ = buffer.write_char(COMMAND_SEPARATOR);
+            }
+            Ok(buffer)
+        },
+    )
+        .compile_bytecode(buffer)?;
+
+    Ok(buffer)
+}
+
+pub fn fmt_generics(generics: &[(Box<str>, Option<AmvmType>)]) -> String {
+    if generics.is_empty() {
+        return String::new();
+    }
+
+    let generics = generics
+        .iter()
+        .map(|(name, default)| match default {
+            Some(default) => format!("{name} = {default}"),
+            None => name.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    format!("<{generics}>")
+}
+
 impl fmt::Display for Command {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -481,7 +883,8 @@ impl fmt::Display for Command {
                 f.write_fmt(format_args!(": AssignVariable({name}, {value})"))
             }
 
-            Self::Break => f.write_str(": Break"),
+            Self::Break { label } => write!(f, ": Break{}", fmt_label(label)),
+            Self::Continue { label } => write!(f, ": Continue{}", fmt_label(label)),
 
             Self::Builtin { name, args } => {
                 f.write_fmt(format_args!(": Builtin({name}, {args:#?})"))
@@ -513,32 +916,61 @@ impl fmt::Display for Command {
             }
 
             Self::For {
+                label,
                 var,
                 iterator,
                 body,
             } => {
-                writeln!(f, ": For({var} in {iterator})")?;
+                writeln!(f, ": For{}({var} in {iterator})", fmt_label(label))?;
 
                 fmt_body(f, body)
             }
 
             Self::Function {
                 name,
+                is_mutable,
+                generics,
                 args: _,
                 ret,
                 body,
             } => {
-                writeln!(f, ": Function {name}(...) {ret}")?;
+                let kind = if *is_mutable { "mut " } else { "" };
+                writeln!(
+                    f,
+                    ": Function {kind}{name}{}(...) {ret}",
+                    fmt_generics(generics)
+                )?;
 
                 fmt_body(f, body)
             }
 
-            Self::Loop { body } => {
-                f.write_str(": Loop:\n")?;
+            Self::Loop { label, body } => {
+                writeln!(f, ": Loop{}:", fmt_label(label))?;
 
                 fmt_body(f, body)
             }
 
+            Self::Enum { name, variants } => {
+                write!(f, ": Enum {name}")?;
+
+                for (variant, fields) in variants {
+                    write!(f, "\n: Variant {variant} {fields:?}")?;
+                }
+
+                Ok(())
+            }
+
+            Self::Match { value, arms } => {
+                write!(f, ": Match({value})")?;
+
+                for (pattern, body) in arms {
+                    write!(f, "\n: Arm({pattern}):\n")?;
+                    fmt_body(f, body)?;
+                }
+
+                Ok(())
+            }
+
             Self::Meta { pos, code } => {
                 write!(f, ": Meta({pos:?}, {code:?})")
             }
@@ -549,13 +981,66 @@ impl fmt::Display for Command {
             Self::Push { value } => write!(f, ": Push({value})"),
             Self::Puts { value } => write!(f, ": Puts({value})"),
             Self::Return { value } => write!(f, ": Return {value}"),
+            Self::Static { name, value } => write!(f, ": Static ${name} = {value}"),
+            Self::Throw { value } => write!(f, ": Throw({value})"),
+            Self::Try {
+                body,
+                catch,
+                finally,
+            } => {
+                f.write_str(": Try:\n")?;
+                fmt_body(f, body)?;
+
+                if let Some((name, body)) = catch {
+                    write!(f, "\n: Catch(${name}):\n")?;
+                    fmt_body(f, body)?;
+                }
+
+                if let Some(finally) = finally {
+                    f.write_str("\n: Finally:\n")?;
+                    fmt_body(f, finally)?;
+                }
+
+                Ok(())
+            }
+            Self::Type { name, ty } => write!(f, ": Type {name} = {}", ty.flat_name()),
+            Self::While {
+                label,
+                condition,
+                body,
+                is_do,
+            } => {
+                let kind = if *is_do { "DoWhile" } else { "While" };
+                writeln!(f, ": {kind}{}({condition}):", fmt_label(label))?;
+
+                fmt_body(f, body)
+            }
+            Self::Yield { value } => write!(f, ": Yield({value})"),
+            Self::Spawn { name, args } => {
+                write!(f, ": Spawn({name}, {args:#?})")
+            }
+            Self::Await { value } => write!(f, ": Await({value})"),
 
             Self::Scope { body } => {
                 writeln!(f, ": Scope:")?;
 
                 fmt_body(f, body)
             }
-            Self::Struct { name, body } => write!(f, ": Struct {name} {body:#?}"),
+            Self::Struct {
+                name,
+                generics,
+                inherits,
+                body,
+            } => {
+                write!(f, ": Struct {name}{}", fmt_generics(generics))?;
+
+                if !inherits.is_empty() {
+                    f.write_str(" : ")?;
+                    AmvmType::fmt_args(inherits, f)?;
+                }
+
+                write!(f, " {body:#?}")
+            }
         }
     }
 }
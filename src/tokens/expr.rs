@@ -13,7 +13,19 @@ create_bytes! {0x10;
     EXPR_REF,
     EXPR_STRUCT,
     EXPR_VALUE,
-    EXPR_VAR
+    EXPR_VAR,
+    EXPR_TUPLE,
+    EXPR_LIST,
+    EXPR_MAP,
+    EXPR_OPT_PROP,
+    EXPR_COALESCE,
+    EXPR_VARIANT,
+    EXPR_IS_VARIANT,
+    EXPR_FN,
+    EXPR_NEG,
+    EXPR_AND,
+    EXPR_OR,
+    EXPR_NOT
 }
 
 create_bytes! {0x0;
@@ -27,7 +39,16 @@ create_bytes! {0x0;
     EXPR_KIND_GREATER_THAN,
     EXPR_KIND_GREATER_THAN_EQUAL,
     EXPR_KIND_LESS_THAN,
-    EXPR_KIND_LESS_THAN_EQUAL
+    EXPR_KIND_LESS_THAN_EQUAL,
+
+    EXPR_KIND_DIV,
+    EXPR_KIND_MOD,
+    EXPR_KIND_POW,
+    EXPR_KIND_BIT_AND,
+    EXPR_KIND_BIT_OR,
+    EXPR_KIND_BIT_XOR,
+    EXPR_KIND_SHL,
+    EXPR_KIND_SHR
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,6 +56,16 @@ pub enum BinaryKind {
     Add,
     Sub,
     Mult,
+    Div,
+    Mod,
+    Pow,
+
+    // Bitwise
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 
     // Conditionals
     Equal,
@@ -53,6 +84,15 @@ impl Compilable for BinaryKind {
             Self::Add => EXPR_KIND_ADD,
             Self::Sub => EXPR_KIND_SUB,
             Self::Mult => EXPR_KIND_MUL,
+            Self::Div => EXPR_KIND_DIV,
+            Self::Mod => EXPR_KIND_MOD,
+            Self::Pow => EXPR_KIND_POW,
+            // Bitwise
+            Self::BitAnd => EXPR_KIND_BIT_AND,
+            Self::BitOr => EXPR_KIND_BIT_OR,
+            Self::BitXor => EXPR_KIND_BIT_XOR,
+            Self::Shl => EXPR_KIND_SHL,
+            Self::Shr => EXPR_KIND_SHR,
             // Conditionals
             Self::Equal => EXPR_KIND_EQUAL,
             Self::NotEqual => EXPR_KIND_NOT_EQUAL,
@@ -68,13 +108,38 @@ impl Compilable for BinaryKind {
 
 #[derive(Debug, Clone)]
 pub enum CommandExpression {
+    /// Only evaluates the right side when the left one is true
+    And(Box<CommandExpression>, Box<CommandExpression>),
     Binary(BinaryKind, Box<CommandExpression>, Box<CommandExpression>),
+    Coalesce(Box<CommandExpression>, Box<CommandExpression>),
+    /// Anonymous function
+    Function(
+        Vec<(Box<str>, VariableKind, AmvmType)>,
+        AmvmType,
+        Vec<Command>,
+    ),
+    IsVariant(Box<CommandExpression>, AmvmType, Box<str>),
+    List(Vec<CommandExpression>),
+    Map(Vec<(CommandExpression, CommandExpression)>),
+    Negate(Box<CommandExpression>),
+    Not(Box<CommandExpression>),
+    /// Only evaluates the right side when the left one is false
+    Or(Box<CommandExpression>, Box<CommandExpression>),
     Prev,
     Property(Box<CommandExpression>, Box<CommandExpression>),
-    Range(Box<CommandExpression>, Box<CommandExpression>),
+    OptionalProperty(Box<CommandExpression>, Box<CommandExpression>),
+    /// Start, end, optional step and whether the end is included
+    Range(
+        Box<CommandExpression>,
+        Box<CommandExpression>,
+        Option<Box<CommandExpression>>,
+        bool,
+    ),
     Ref(VariableKind, Box<CommandExpression>),
     Struct(AmvmType, Vec<(Box<str>, CommandExpression)>),
+    Tuple(Vec<CommandExpression>),
     Value(Value),
+    Variant(AmvmType, Box<str>, Vec<(Box<str>, CommandExpression)>),
     Var(String),
 }
 
@@ -82,12 +147,67 @@ impl std::fmt::Display for CommandExpression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Binary(kind, a, b) => write!(f, "{a} {kind:?} {b}"),
+            Self::Coalesce(a, b) => write!(f, "({a}) ?? ({b})"),
+            Self::Negate(value) => write!(f, "-({value})"),
+            Self::And(a, b) => write!(f, "({a}) && ({b})"),
+            Self::Or(a, b) => write!(f, "({a}) || ({b})"),
+            Self::Not(value) => write!(f, "!({value})"),
+            Self::Function(args, ret, body) => {
+                f.write_str("fn(")?;
+                for (i, (name, kind, ty)) in args.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{kind} {name}: {}", ty.flat_name())?;
+                }
+                write!(f, ") {} {{ {} commands }}", ret.flat_name(), body.len())
+            }
+            Self::IsVariant(value, t, variant) => write!(f, "{value} is {t}.{variant}"),
+            Self::List(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" ")?;
+                    }
+                    item.fmt(f)?;
+                }
+                f.write_str("]")
+            }
+            Self::Map(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, " {key} => {value}")?;
+                }
+                f.write_str(" }")
+            }
             Self::Prev => f.write_str("Prev"),
             Self::Property(a, b) => write!(f, "({a})[{b}]"),
-            Self::Range(a, b) => write!(f, "({a}) .. ({b})"),
+            Self::OptionalProperty(a, b) => write!(f, "({a})?[{b}]"),
+            Self::Range(a, b, step, inclusive) => {
+                let op = if *inclusive { "..=" } else { ".." };
+                write!(f, "({a}) {op} ({b})")?;
+                if let Some(step) = step {
+                    write!(f, " step ({step})")?;
+                }
+                Ok(())
+            }
             Self::Ref(kind, var) => write!(f, "&{kind} {var}"),
             Self::Struct(t, data) => write!(f, "{t} {data:?}"),
+            Self::Tuple(items) => {
+                f.write_str("(")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    item.fmt(f)?;
+                }
+                f.write_str(")")
+            }
             Self::Value(v) => (v as &dyn std::fmt::Display).fmt(f),
+            Self::Variant(t, variant, data) => write!(f, "{t}.{variant} {data:?}"),
             Self::Var(v) => write!(f, "${v}"),
         }
     }
@@ -116,6 +236,15 @@ impl CommandExpression {
             k if k == EXPR_KIND_ADD => Self::condition(parser, BinaryKind::Add),
             k if k == EXPR_KIND_SUB => Self::condition(parser, BinaryKind::Sub),
             k if k == EXPR_KIND_MUL => Self::condition(parser, BinaryKind::Mult),
+            k if k == EXPR_KIND_DIV => Self::condition(parser, BinaryKind::Div),
+            k if k == EXPR_KIND_MOD => Self::condition(parser, BinaryKind::Mod),
+            k if k == EXPR_KIND_POW => Self::condition(parser, BinaryKind::Pow),
+
+            k if k == EXPR_KIND_BIT_AND => Self::condition(parser, BinaryKind::BitAnd),
+            k if k == EXPR_KIND_BIT_OR => Self::condition(parser, BinaryKind::BitOr),
+            k if k == EXPR_KIND_BIT_XOR => Self::condition(parser, BinaryKind::BitXor),
+            k if k == EXPR_KIND_SHL => Self::condition(parser, BinaryKind::Shl),
+            k if k == EXPR_KIND_SHR => Self::condition(parser, BinaryKind::Shr),
 
             k if k == EXPR_KIND_EQUAL => Self::condition(parser, BinaryKind::Equal),
             k if k == EXPR_KIND_NOT_EQUAL => Self::condition(parser, BinaryKind::NotEqual),
@@ -150,11 +279,59 @@ impl CommandExpression {
 
                 Ok((parser, CommandExpression::Property(a.into(), b.into())))
             }
+            _ if b == EXPR_OPT_PROP => {
+                let (parser, a) = CommandExpression::visit(parser)?;
+                let (parser, b) = CommandExpression::visit(parser)?;
+
+                Ok((
+                    parser,
+                    CommandExpression::OptionalProperty(a.into(), b.into()),
+                ))
+            }
+            _ if b == EXPR_AND => {
+                let (parser, a) = CommandExpression::visit(parser)?;
+                let (parser, b) = CommandExpression::visit(parser)?;
+
+                Ok((parser, CommandExpression::And(a.into(), b.into())))
+            }
+            _ if b == EXPR_OR => {
+                let (parser, a) = CommandExpression::visit(parser)?;
+                let (parser, b) = CommandExpression::visit(parser)?;
+
+                Ok((parser, CommandExpression::Or(a.into(), b.into())))
+            }
+            _ if b == EXPR_NOT => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+
+                Ok((parser, CommandExpression::Not(value.into())))
+            }
+            _ if b == EXPR_NEG => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+
+                Ok((parser, CommandExpression::Negate(value.into())))
+            }
+            _ if b == EXPR_COALESCE => {
+                let (parser, a) = CommandExpression::visit(parser)?;
+                let (parser, b) = CommandExpression::visit(parser)?;
+
+                Ok((parser, CommandExpression::Coalesce(a.into(), b.into())))
+            }
             _ if b == EXPR_RANGE => {
                 let (parser, a) = CommandExpression::visit(parser)?;
                 let (parser, b) = CommandExpression::visit(parser)?;
+                let (parser, inclusive) = parser::anychar(parser)?;
+                let (parser, has_step) = parser::anychar(parser)?;
+                let (parser, step) = if has_step == '\x01' {
+                    let (parser, step) = CommandExpression::visit(parser)?;
+                    (parser, Some(Box::new(step)))
+                } else {
+                    (parser, None)
+                };
 
-                Ok((parser, CommandExpression::Range(a.into(), b.into())))
+                Ok((
+                    parser,
+                    CommandExpression::Range(a.into(), b.into(), step, inclusive == '\x01'),
+                ))
             }
             _ if b == EXPR_REF => {
                 let (parser, kind) = Command::visit_kind(parser)?;
@@ -180,6 +357,64 @@ impl CommandExpression {
 
                 Ok((parser, CommandExpression::Struct(r#type, data)))
             }
+            _ if b == EXPR_TUPLE => {
+                let (parser, items) = Value::visit_slice(parser, CommandExpression::visit)?;
+
+                Ok((parser, CommandExpression::Tuple(items)))
+            }
+            _ if b == EXPR_LIST => {
+                let (parser, items) = Value::visit_slice(parser, CommandExpression::visit)?;
+
+                Ok((parser, CommandExpression::List(items)))
+            }
+            _ if b == EXPR_MAP => {
+                let (parser, entries) = Value::visit_slice(parser, |parser| {
+                    let (parser, key) = CommandExpression::visit(parser)?;
+                    let (parser, value) = CommandExpression::visit(parser)?;
+
+                    Ok((parser, (key, value)))
+                })?;
+
+                Ok((parser, CommandExpression::Map(entries)))
+            }
+            _ if b == EXPR_VARIANT => {
+                let (parser, r#type) = AmvmType::visit(parser)?;
+                let (parser, variant) = Value::visit_string(parser)?;
+                let (parser, data) = Value::visit_slice(parser, |parser| {
+                    let (parser, field) = Value::visit_string(parser)?;
+                    let (parser, value) = CommandExpression::visit(parser)?;
+
+                    Ok((parser, (Box::from(field), value)))
+                })?;
+
+                Ok((
+                    parser,
+                    CommandExpression::Variant(r#type, Box::from(variant), data),
+                ))
+            }
+            _ if b == EXPR_FN => {
+                let (parser, args) = Value::visit_slice(parser, |parser| {
+                    let (parser, name) = Value::visit_string(parser)?;
+                    let (parser, kind) = Command::visit_kind(parser)?;
+                    let (parser, ty) = AmvmType::visit(parser)?;
+
+                    Ok((parser, (Box::from(name), kind, ty)))
+                })?;
+                let (parser, ret) = AmvmType::visit(parser)?;
+                let (parser, body) = Command::visit_scope(parser)?;
+
+                Ok((parser, CommandExpression::Function(args, ret, body)))
+            }
+            _ if b == EXPR_IS_VARIANT => {
+                let (parser, value) = CommandExpression::visit(parser)?;
+                let (parser, r#type) = AmvmType::visit(parser)?;
+                let (parser, variant) = Value::visit_string(parser)?;
+
+                Ok((
+                    parser,
+                    CommandExpression::IsVariant(value.into(), r#type, Box::from(variant)),
+                ))
+            }
             _ if b == EXPR_VALUE => {
                 let (parser, value) = Value::visit(parser)?;
 
@@ -209,16 +444,69 @@ impl Compilable for CommandExpression {
                 buffer = a.compile_bytecode(buffer)?;
                 buffer = b.compile_bytecode(buffer)?;
             }
+            Self::List(items) => {
+                _ = buffer.write_char(EXPR_LIST);
+                buffer = Value::compile_slice(buffer, items)?;
+            }
+            Self::Map(entries) => {
+                _ = buffer.write_char(EXPR_MAP);
+                buffer = (
+                    entries,
+                    |mut buffer: String,
+                     entry: &(CommandExpression, CommandExpression)|
+                     -> CompileResult {
+                        buffer = entry.0.compile_bytecode(buffer)?;
+                        buffer = entry.1.compile_bytecode(buffer)?;
+                        Ok(buffer)
+                    },
+                )
+                    .compile_bytecode(buffer)?;
+            }
             Self::Prev => _ = buffer.write_char(EXPR_PREV),
             Self::Property(a, b) => {
                 _ = buffer.write_char(EXPR_PROP);
                 buffer = a.compile_bytecode(buffer)?;
                 buffer = b.compile_bytecode(buffer)?;
             }
-            Self::Range(a, b) => {
+            Self::OptionalProperty(a, b) => {
+                _ = buffer.write_char(EXPR_OPT_PROP);
+                buffer = a.compile_bytecode(buffer)?;
+                buffer = b.compile_bytecode(buffer)?;
+            }
+            Self::And(a, b) => {
+                _ = buffer.write_char(EXPR_AND);
+                buffer = a.compile_bytecode(buffer)?;
+                buffer = b.compile_bytecode(buffer)?;
+            }
+            Self::Or(a, b) => {
+                _ = buffer.write_char(EXPR_OR);
+                buffer = a.compile_bytecode(buffer)?;
+                buffer = b.compile_bytecode(buffer)?;
+            }
+            Self::Not(value) => {
+                _ = buffer.write_char(EXPR_NOT);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Negate(value) => {
+                _ = buffer.write_char(EXPR_NEG);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Coalesce(a, b) => {
+                _ = buffer.write_char(EXPR_COALESCE);
+                buffer = a.compile_bytecode(buffer)?;
+                buffer = b.compile_bytecode(buffer)?;
+            }
+            Self::Range(a, b, step, inclusive) => {
                 _ = buffer.write_char(EXPR_RANGE);
                 buffer = a.compile_bytecode(buffer)?;
                 buffer = b.compile_bytecode(buffer)?;
+                _ = buffer.write_char(if *inclusive { '\x01' } else { '\x00' });
+                if let Some(step) = step {
+                    _ = buffer.write_char('\x01');
+                    buffer = step.compile_bytecode(buffer)?;
+                } else {
+                    _ = buffer.write_char('\x00');
+                }
             }
             Self::Ref(kind, var) => {
                 _ = buffer.write_char(EXPR_REF);
@@ -238,10 +526,51 @@ impl Compilable for CommandExpression {
                 )
                     .compile_bytecode(buffer)?;
             }
+            Self::Tuple(items) => {
+                _ = buffer.write_char(EXPR_TUPLE);
+                buffer = Value::compile_slice(buffer, items)?;
+            }
             Self::Value(v) => {
                 _ = buffer.write_char(EXPR_VALUE);
                 buffer = v.compile_bytecode(buffer)?;
             }
+            Self::Variant(r#type, variant, data) => {
+                _ = buffer.write_char(EXPR_VARIANT);
+                buffer = r#type.compile_bytecode(buffer)?;
+                buffer = variant.compile_bytecode(buffer)?;
+                buffer = (
+                    data,
+                    |mut buffer: String, f: &(Box<str>, CommandExpression)| -> CompileResult {
+                        buffer = f.0.compile_bytecode(buffer)?;
+                        buffer = f.1.compile_bytecode(buffer)?;
+                        Ok(buffer)
+                    },
+                )
+                    .compile_bytecode(buffer)?;
+            }
+            Self::Function(args, ret, body) => {
+                _ = buffer.write_char(EXPR_FN);
+                buffer = (
+                    args,
+                    |mut buffer: String,
+                     arg: &(Box<str>, VariableKind, AmvmType)|
+                     -> CompileResult {
+                        buffer = arg.0.compile_bytecode(buffer)?;
+                        buffer = arg.1.compile_bytecode(buffer)?;
+                        buffer = arg.2.compile_bytecode(buffer)?;
+                        Ok(buffer)
+                    },
+                )
+                    .compile_bytecode(buffer)?;
+                buffer = ret.compile_bytecode(buffer)?;
+                buffer = body.compile_bytecode(buffer)?;
+            }
+            Self::IsVariant(value, r#type, variant) => {
+                _ = buffer.write_char(EXPR_IS_VARIANT);
+                buffer = value.compile_bytecode(buffer)?;
+                buffer = r#type.compile_bytecode(buffer)?;
+                buffer = variant.compile_bytecode(buffer)?;
+            }
             Self::Var(var) => {
                 _ = buffer.write_char(EXPR_VAR);
                 buffer = var.compile_bytecode(buffer)?;
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use crate::tokens::Value;
+
+/// Values that can be used as keys of a [ValueMap].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ValueMapKey {
+    Bool(bool),
+    Char(char),
+    I16(i16),
+    String(String),
+    U8(u8),
+    Usize(usize),
+}
+
+impl ValueMapKey {
+    pub fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Bool(v) => Some(Self::Bool(*v)),
+            Value::Char(v) => Some(Self::Char(*v)),
+            Value::I16(v) => Some(Self::I16(*v)),
+            Value::String(v) => Some(Self::String(v.clone())),
+            Value::U8(v) => Some(Self::U8(*v)),
+            Value::Usize(v) => Some(Self::Usize(*v)),
+            Value::Ref(var) => Self::from_value(&var.read()),
+            _ => None,
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Bool(v) => Value::Bool(*v),
+            Self::Char(v) => Value::Char(*v),
+            Self::I16(v) => Value::I16(*v),
+            Self::String(v) => Value::String(v.clone()),
+            Self::U8(v) => Value::U8(*v),
+            Self::Usize(v) => Value::Usize(*v),
+        }
+    }
+}
+
+/// Map that keeps the order in which its keys were inserted.
+#[derive(Debug, Clone, Default)]
+pub struct ValueMap {
+    indexes: HashMap<ValueMapKey, usize>,
+    entries: Vec<(ValueMapKey, Value)>,
+}
+
+impl ValueMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &ValueMapKey) -> Option<&Value> {
+        self.indexes.get(key).map(|idx| &self.entries[*idx].1)
+    }
+
+    pub fn contains_key(&self, key: &ValueMapKey) -> bool {
+        self.indexes.contains_key(key)
+    }
+
+    /// Replacing the value of an existing key keeps its position.
+    pub fn insert(&mut self, key: ValueMapKey, value: Value) -> Option<Value> {
+        if let Some(idx) = self.indexes.get(&key) {
+            return Some(std::mem::replace(&mut self.entries[*idx].1, value));
+        }
+
+        self.indexes.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+
+        None
+    }
+
+    pub fn remove(&mut self, key: &ValueMapKey) -> Option<Value> {
+        let idx = self.indexes.remove(key)?;
+        let (_, value) = self.entries.remove(idx);
+
+        for (key, _) in &self.entries[idx..] {
+            if let Some(i) = self.indexes.get_mut(key) {
+                *i -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &ValueMapKey> {
+        self.entries.iter().map(|(key, _)| key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(ValueMapKey, Value)> {
+        self.entries.iter()
+    }
+}
@@ -9,18 +9,24 @@ pub use expr::{EXPR_VALUE, EXPR_VAR};
 mod header;
 pub use header::{AmvmHeader, AmvmTypeCasting};
 
+mod map;
+pub use map::{ValueMap, ValueMapKey};
+
+mod pattern;
+pub use pattern::CommandPattern;
+
 mod program;
 pub use program::Program;
 
 mod scope;
-pub use scope::{AmvmMeta, AmvmScope};
+pub use scope::{AmvmFrame, AmvmMeta, AmvmScope};
 
 mod r#type;
-pub use r#type::{AmvmPrimitiveType, AmvmType, AmvmTypeDefinition};
+pub use r#type::{AmvmPrimitiveType, AmvmType, AmvmTypeDefinition, EnumVariant};
 pub use r#type::{TYPE_ANON, TYPE_CUSTOM, TYPE_STRING, TYPE_TUPLE, TYPE_U8, TYPE_UNION};
 
 mod value;
-pub use value::{Value, ValueFun, ValueObject};
+pub use value::{FunEnv, Value, ValueFun, ValueObject};
 pub use value::{
     VALUE_CHAR, VALUE_F32, VALUE_FUN, VALUE_I16, VALUE_OBJECT, VALUE_STRING, VALUE_U8,
     VALUE_UNDEFINED,
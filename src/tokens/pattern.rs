@@ -0,0 +1,103 @@
+use std::fmt;
+
+use crate::{
+    create_bytes,
+    parser::{self, Parser, ParserResult},
+    tokens::{AmvmType, Value},
+    Compilable, CompileResult,
+};
+
+create_bytes! {0;
+    PATTERN_WILDCARD,
+    PATTERN_VALUE,
+    PATTERN_TYPE,
+    PATTERN_STRUCT
+}
+
+#[derive(Debug, Clone)]
+pub enum CommandPattern {
+    /// `_`
+    Wildcard,
+    /// `1u8`, `"text"`, `'c`..
+    Value(Value),
+    /// `#u8`, only checks the type of the value
+    Type(AmvmType),
+    /// `#Person { name $n }`, binds each field to a variable
+    Struct(AmvmType, Vec<(Box<str>, Box<str>)>),
+}
+
+impl CommandPattern {
+    pub fn visit(parser: Parser<'_>) -> ParserResult<'_, Self> {
+        let (parser, b) = parser::anychar(parser)
+            .map_err(parser.nom_err_with_context("Expected pattern kind"))?;
+
+        match b {
+            _ if b == PATTERN_WILDCARD => Ok((parser, CommandPattern::Wildcard)),
+            _ if b == PATTERN_VALUE => {
+                let (parser, value) = Value::visit(parser)?;
+                Ok((parser, CommandPattern::Value(value)))
+            }
+            _ if b == PATTERN_TYPE => {
+                let (parser, ty) = AmvmType::visit(parser)?;
+                Ok((parser, CommandPattern::Type(ty)))
+            }
+            _ if b == PATTERN_STRUCT => {
+                let (parser, ty) = AmvmType::visit(parser)?;
+                let (parser, fields) = Value::visit_slice(parser, |parser| {
+                    let (parser, field) = Value::visit_string(parser)?;
+                    let (parser, var) = Value::visit_string(parser)?;
+
+                    Ok((parser, (Box::from(field), Box::from(var))))
+                })?;
+
+                Ok((parser, CommandPattern::Struct(ty, fields)))
+            }
+            _ => Err(parser.error(
+                parser::VerboseErrorKind::Context("Unknown pattern kind"),
+                true,
+            )),
+        }
+    }
+}
+
+impl Compilable for CommandPattern {
+    fn compile_bytecode(&self, mut buffer: String) -> CompileResult {
+        use std::fmt::Write;
+
+        match self {
+            Self::Wildcard => _ = buffer.write_char(PATTERN_WILDCARD),
+            Self::Value(value) => {
+                _ = buffer.write_char(PATTERN_VALUE);
+                buffer = value.compile_bytecode(buffer)?;
+            }
+            Self::Type(ty) => {
+                _ = buffer.write_char(PATTERN_TYPE);
+                buffer = ty.compile_bytecode(buffer)?;
+            }
+            Self::Struct(ty, fields) => {
+                _ = buffer.write_char(PATTERN_STRUCT);
+                buffer = ty.compile_bytecode(buffer)?;
+                buffer = fields.compile_bytecode(buffer)?;
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
+impl fmt::Display for CommandPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Wildcard => f.write_str("_"),
+            Self::Value(value) => value.fmt(f),
+            Self::Type(ty) => f.write_str(&ty.flat_name()),
+            Self::Struct(ty, fields) => {
+                write!(f, "{} {{", ty.flat_name())?;
+                for (field, var) in fields {
+                    write!(f, " {field} ${var}")?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
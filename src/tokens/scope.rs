@@ -1,8 +1,11 @@
+use std::cell::RefCell;
 use std::fmt;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
-use crate::runtime::AmvmError;
+use crate::runtime::limits::Usage;
+use crate::runtime::tasks::Executor;
+use crate::runtime::{AmvmError, AmvmVariable, RuntimeOptions};
 use crate::CompileResult;
 use crate::{
     runtime::Context,
@@ -20,12 +23,8 @@ pub struct AmvmMeta {
 }
 
 impl AmvmMeta {
-    pub fn display(&self, is_original: bool, is_debug: bool) -> String {
-        use fmt::Write;
-        let mut f = String::new();
-
-        let debug_char = if is_debug { "> " } else { "" };
-
+    /// `file:line:column` of the code
+    pub fn location(&self, is_original: bool) -> String {
         let file_name = if is_original {
             self.file_name.0.as_ref().map(|x| x.as_ref())
         } else {
@@ -33,8 +32,17 @@ impl AmvmMeta {
         }
         .unwrap_or("<anonymous>");
 
-        let position = format!("{}:{}", self.pos.0, self.pos.1);
-        _ = writeln!(f, "\x1b[1;34m{debug_char}--> {file_name}:{position}\x1b[0m");
+        format!("{file_name}:{}:{}", self.pos.0, self.pos.1)
+    }
+
+    pub fn display(&self, is_original: bool, is_debug: bool) -> String {
+        use fmt::Write;
+        let mut f = String::new();
+
+        let debug_char = if is_debug { "> " } else { "" };
+
+        let location = self.location(is_original);
+        _ = writeln!(f, "\x1b[1;34m{debug_char}--> {location}\x1b[0m");
 
         let line = self.pos.0.to_string();
         let line_pad = " ".repeat(line.len());
@@ -70,6 +78,47 @@ impl AmvmMeta {
     }
 }
 
+/// Call to a function that is being run
+#[derive(Debug, Clone)]
+pub struct AmvmFrame {
+    /// Name of the function, as it was called
+    pub name: Box<str>,
+    /// Where it was called from, when the code has meta
+    pub call_site: Option<Rc<AmvmMeta>>,
+    pub args: Vec<(Box<str>, AmvmVariable)>,
+    /// Frame of the caller, `None` when called from the top level
+    pub parent: Option<Rc<AmvmFrame>>,
+}
+
+impl AmvmFrame {
+    /// Frames from this one to the outermost call
+    pub fn iter(self: &Rc<Self>) -> impl Iterator<Item = &Rc<AmvmFrame>> {
+        std::iter::successors(Some(self), |frame| frame.parent.as_ref())
+    }
+}
+
+impl fmt::Display for AmvmFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MAX_ARG_LEN: usize = 32;
+
+        write!(f, "{}(", self.name)?;
+        for (i, (name, value)) in self.args.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+
+            let value = value.read().to_string();
+            if value.chars().count() > MAX_ARG_LEN {
+                let value = value.chars().take(MAX_ARG_LEN).collect::<String>();
+                write!(f, "{name}: {value}...")?;
+            } else {
+                write!(f, "{name}: {value}")?;
+            }
+        }
+        f.write_str(")")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct AmvmScope {
     pub file_name: (Option<Box<str>>, Option<Box<str>>),
@@ -78,6 +127,15 @@ pub struct AmvmScope {
     pub header: Rc<AmvmHeader>,
     pub body: Rc<Vec<Command>>,
     pub context: Arc<Mutex<Context>>,
+    /// Tasks of the whole program
+    pub tasks: Rc<RefCell<Executor>>,
+    pub options: Rc<RuntimeOptions>,
+    /// Resources used by the whole program
+    pub usage: Rc<Usage>,
+    /// Number of calls being run
+    pub depth: usize,
+    /// Call being run, `None` at the top level
+    pub frame: Option<Rc<AmvmFrame>>,
 }
 
 impl AmvmScope {
@@ -95,10 +153,36 @@ impl AmvmScope {
             header: Rc::clone(header),
             body: Rc::new(body),
             context: Arc::new(Mutex::new(ctx)),
+            tasks: Default::default(),
+            options: Default::default(),
+            usage: Default::default(),
+            depth: 0,
+            frame: None,
         }
     }
 
     pub fn create_sub(&self, body: Vec<Command>) -> Self {
+        self.create_sub_shared(&Rc::new(body))
+    }
+
+    /// Like [AmvmScope::create_sub], for a body run more than once.
+    pub fn create_sub_shared(&self, body: &Rc<Vec<Command>>) -> Self {
+        let context = Context::create_sub(Arc::clone(&self.context));
+        self.sub_with(Rc::clone(body), context)
+    }
+
+    /// Like [AmvmScope::create_sub], but the variables are looked up in
+    /// `upper` instead of this scope. Used to run functions in the
+    /// context where they were defined.
+    pub fn create_sub_from(&self, body: Vec<Command>, upper: &Arc<Mutex<Context>>) -> Self {
+        let context = Context::create_sub(Arc::clone(upper));
+        self.sub_with(Rc::new(body), context)
+    }
+
+    fn sub_with(&self, body: Rc<Vec<Command>>, context: Context) -> Self {
+        let context = Arc::new(Mutex::new(context));
+        self.usage.register(&self.options, &context);
+
         Self {
             file_name: self.file_name.clone(),
             meta: None,
@@ -112,8 +196,13 @@ impl AmvmScope {
                 })
                 .or(self.backtrace.clone()),
             header: Rc::clone(&self.header),
-            body: Rc::new(body),
-            context: Arc::new(Mutex::new(Context::create_sub(self.context.clone()))),
+            body,
+            context,
+            tasks: Rc::clone(&self.tasks),
+            options: Rc::clone(&self.options),
+            usage: Rc::clone(&self.usage),
+            depth: self.depth,
+            frame: self.frame.clone(),
         }
     }
 
@@ -137,7 +226,7 @@ impl AmvmScope {
     }
 
     pub fn error(&mut self, ctx: &'static str) -> AmvmError {
-        AmvmError::Other(self.full_backtrace(), ctx)
+        AmvmError::Other(self.full_backtrace(), self.frame.clone(), ctx)
     }
 }
 
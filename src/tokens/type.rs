@@ -4,7 +4,7 @@ use crate::CompileResult;
 use crate::{
     create_bytes,
     parser::{self, Parser, ParserResult},
-    tokens::Value,
+    tokens::{Value, COMMAND_SEPARATOR},
     Compilable,
 };
 
@@ -18,11 +18,21 @@ create_bytes! {0;
     TYPE_BOOL,
     TYPE_FUN,
     TYPE_STRING,
-    TYPE_U8
+    TYPE_U8,
+
+    TYPE_GENERIC,
+    TYPE_LIST,
+    TYPE_NULLABLE
 }
 
+/// Name of an enum variant and its fields.
+pub type EnumVariant<S = Box<str>> = (S, Vec<(S, AmvmType)>);
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AmvmTypeDefinition {
+    Enum {
+        variants: Vec<EnumVariant>,
+    },
     Inheritance(Vec<AmvmType>, Box<AmvmTypeDefinition>),
     Struct {
         generics: Vec<(Box<str>, Option<AmvmType>)>,
@@ -41,15 +51,26 @@ pub enum AmvmPrimitiveType {
 pub enum AmvmType {
     Anonymous,
 
+    List(Box<AmvmType>),
+    Nullable(Box<AmvmType>),
     Tuple(Vec<AmvmType>),
     Union(Box<AmvmType>, Box<AmvmType>),
 
     Fun(Vec<AmvmType>, Box<AmvmType>),
+    Generic(Box<str>, Vec<AmvmType>),
     Named(Box<str>),
     Primitive(AmvmPrimitiveType),
 }
 
 impl AmvmType {
+    /// Name of the declaration behind this type, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Named(name) | Self::Generic(name, _) => Some(name),
+            _ => None,
+        }
+    }
+
     pub fn flat_name_args(args: &Vec<AmvmType>) -> String {
         use std::fmt::Write;
 
@@ -71,6 +92,8 @@ impl AmvmType {
             Self::Anonymous => String::from("#"),
             Self::Named(name) => format!("#{name}"),
 
+            Self::List(ty) => format!("#[{}]", ty.flat_name()),
+            Self::Nullable(ty) => format!("{}?", ty.flat_name()),
             Self::Tuple(tuple) => format!(
                 "#({})",
                 tuple
@@ -86,6 +109,7 @@ impl AmvmType {
                 Self::flat_name_args(args),
                 ret.flat_name()
             ),
+            Self::Generic(name, args) => format!("#{name}<{}>", Self::flat_name_args(args)),
             Self::Primitive(name) => format!("#{name}"),
         }
     }
@@ -120,6 +144,8 @@ impl fmt::Display for AmvmType {
             Self::Named(name) => f.write_str(name),
 
             Self::Union(a, b) => write!(f, "{a} + {b}"),
+            Self::List(ty) => write!(f, "[{ty}]"),
+            Self::Nullable(ty) => write!(f, "{ty}?"),
             Self::Tuple(types) => {
                 let mut tuple = f.debug_tuple("Tuple");
                 for r#type in types {
@@ -134,6 +160,11 @@ impl fmt::Display for AmvmType {
                 AmvmType::fmt_args(args, f)?;
                 write!(f, ") -> {ret}")
             }
+            Self::Generic(name, args) => {
+                write!(f, "{name}<")?;
+                AmvmType::fmt_args(args, f)?;
+                f.write_str(">")
+            }
             Self::Primitive(name) => name.fmt(f),
             // Self::Struct(_) => todo!(),
         }
@@ -156,6 +187,14 @@ impl Compilable for AmvmType {
                 buffer = a.compile_bytecode(buffer)?;
                 buffer = b.compile_bytecode(buffer)?;
             }
+            Self::List(ty) => {
+                _ = buffer.write_char(TYPE_LIST);
+                buffer = ty.compile_bytecode(buffer)?;
+            }
+            Self::Nullable(ty) => {
+                _ = buffer.write_char(TYPE_NULLABLE);
+                buffer = ty.compile_bytecode(buffer)?;
+            }
             Self::Tuple(fields) => {
                 _ = buffer.write_char(TYPE_TUPLE);
                 buffer = Value::compile_slice(buffer, fields)?;
@@ -166,6 +205,11 @@ impl Compilable for AmvmType {
                 buffer = Value::compile_slice(buffer, args)?;
                 buffer = ret.compile_bytecode(buffer)?;
             }
+            Self::Generic(name, args) => {
+                _ = buffer.write_char(TYPE_GENERIC);
+                buffer = name.compile_bytecode(buffer)?;
+                buffer = Value::compile_slice(buffer, args)?;
+            }
 
             Self::Primitive(ty) => {
                 _ = buffer.write_char(match ty {
@@ -195,6 +239,14 @@ impl AmvmType {
                 let (parser, b) = AmvmType::visit(parser)?;
                 (parser, AmvmType::Union(Box::new(a), Box::new(b)))
             }
+            _ if c == TYPE_LIST => {
+                let (parser, ty) = AmvmType::visit(parser)?;
+                (parser, AmvmType::List(Box::new(ty)))
+            }
+            _ if c == TYPE_NULLABLE => {
+                let (parser, ty) = AmvmType::visit(parser)?;
+                (parser, AmvmType::Nullable(Box::new(ty)))
+            }
             _ if c == TYPE_TUPLE => {
                 let (parser, fields) = Value::visit_slice(parser, AmvmType::visit)?;
                 (parser, AmvmType::Tuple(fields))
@@ -206,6 +258,12 @@ impl AmvmType {
 
                 (parser, AmvmType::Fun(args, Box::new(ret)))
             }
+            _ if c == TYPE_GENERIC => {
+                let (parser, name) = Value::visit_string(parser)?;
+                let (parser, args) = Value::visit_slice(parser, AmvmType::visit)?;
+
+                (parser, AmvmType::Generic(Box::from(name), args))
+            }
 
             // Primitives
             _ if c == TYPE_BOOL => (parser, AmvmType::Primitive(AmvmPrimitiveType::Bool)),
@@ -216,3 +274,30 @@ impl AmvmType {
         })
     }
 }
+
+impl AmvmTypeDefinition {
+    pub fn generics(&self) -> Option<&Vec<(Box<str>, Option<AmvmType>)>> {
+        match self {
+            Self::Enum { .. } => None,
+            Self::Inheritance(_, decl) => decl.generics(),
+            Self::Struct { generics, .. } => Some(generics),
+        }
+    }
+
+    pub fn visit_generics(
+        parser: Parser<'_>,
+    ) -> ParserResult<'_, Vec<(Box<str>, Option<AmvmType>)>> {
+        Value::visit_slice(parser, |parser| {
+            let (parser, name) = Value::visit_string(parser)?;
+            let (parser, default) = if parser.peek(0) == Some(COMMAND_SEPARATOR) {
+                let (_, parser) = parser::take(1usize)(parser)?;
+                (parser, None)
+            } else {
+                let (parser, default) = AmvmType::visit(parser)?;
+                (parser, Some(default))
+            };
+
+            Ok((parser, (Box::from(name), default)))
+        })
+    }
+}
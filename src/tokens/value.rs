@@ -1,14 +1,14 @@
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 
-use crate::runtime::{AmvmResult, AmvmVariable};
+use crate::runtime::{AmvmResult, AmvmVariable, Context};
 use crate::utils::CompileResult;
 use crate::{
     create_bytes,
     parser::{self, Parser, ParserResult},
-    tokens::{AmvmType, Command, CommandExpression, COMMAND_SEPARATOR},
+    tokens::{AmvmType, Command, CommandExpression, ValueMap, ValueMapKey, COMMAND_SEPARATOR},
     Compilable,
 };
 
@@ -23,7 +23,11 @@ create_bytes! {0x30;
     VALUE_F32,
     VALUE_OBJECT,
     VALUE_CHAR,
-    VALUE_FUN
+    VALUE_FUN,
+    VALUE_TUPLE,
+    VALUE_LIST,
+    VALUE_USIZE,
+    VALUE_MAP
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +36,53 @@ pub enum ValueObject {
     Native(*mut u32),
     Instance(AmvmType, HashMap<String, Arc<RwLock<Value>>>),
     PropertyMap(HashMap<String, Arc<RwLock<Value>>>),
+    /// Instance of an enum variant
+    Variant(AmvmType, Box<str>, HashMap<String, Arc<RwLock<Value>>>),
+}
+
+/// Context where a function was defined.
+#[derive(Debug)]
+pub enum FunEnv {
+    Shared(Arc<Mutex<Context>>),
+    /// A function stored in the context where it was defined doesn't keep
+    /// it alive, or neither would be dropped. Copies of it do again.
+    Stored(Weak<Mutex<Context>>),
+}
+
+impl FunEnv {
+    pub fn new(context: &Arc<Mutex<Context>>) -> Self {
+        Self::Shared(Arc::clone(context))
+    }
+
+    /// The context, `None` once it was dropped.
+    pub fn get(&self) -> Option<Arc<Mutex<Context>>> {
+        match self {
+            Self::Shared(context) => Some(Arc::clone(context)),
+            Self::Stored(context) => context.upgrade(),
+        }
+    }
+
+    pub fn ptr_eq(&self, context: &Arc<Mutex<Context>>) -> bool {
+        self.as_ptr() == Arc::as_ptr(context)
+    }
+
+    pub fn as_ptr(&self) -> *const Mutex<Context> {
+        match self {
+            Self::Shared(context) => Arc::as_ptr(context),
+            Self::Stored(context) => context.as_ptr(),
+        }
+    }
+}
+
+impl Clone for FunEnv {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Shared(context) => Self::Shared(Arc::clone(context)),
+            Self::Stored(context) => context
+                .upgrade()
+                .map_or_else(|| Self::Stored(Weak::clone(context)), Self::Shared),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -42,30 +93,60 @@ pub enum ValueFun {
         AmvmType,
         Rc<RefCell<dyn FnMut(&mut AmvmScope) -> AmvmResult>>,
     ),
+    /// The last field is the context where the function was defined
     Const(
+        Vec<(Box<str>, Option<AmvmType>)>,
         Vec<(Box<str>, VariableKind, AmvmType)>,
         AmvmType,
-        Vec<Command>,
+        Rc<Vec<Command>>,
+        FunEnv,
     ),
+    /// Like [ValueFun::Const], with the context of its static variables
     Mutable(
+        Vec<(Box<str>, Option<AmvmType>)>,
         Vec<(Box<str>, VariableKind, AmvmType)>,
         AmvmType,
-        Vec<Command>,
+        Rc<Vec<Command>>,
+        FunEnv,
+        Arc<Mutex<Context>>,
     ),
 }
 
+impl ValueFun {
+    /// Function to store in `context`. If it was defined there, it doesn't
+    /// keep `context` alive.
+    pub fn stored_in(self, context: &Arc<Mutex<Context>>) -> Self {
+        match self {
+            Self::Const(generics, args, ret, body, env) if env.ptr_eq(context) => Self::Const(
+                generics,
+                args,
+                ret,
+                body,
+                FunEnv::Stored(Arc::downgrade(context)),
+            ),
+            Self::Mutable(generics, args, ret, body, env, state) if env.ptr_eq(context) => {
+                let env = FunEnv::Stored(Arc::downgrade(context));
+                Self::Mutable(generics, args, ret, body, env, state)
+            }
+            fun => fun,
+        }
+    }
+}
+
 impl std::fmt::Debug for ValueFun {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Native(args, ret, _) => f.debug_tuple("Native").field(args).field(ret).finish(),
-            Self::Const(args, ret, body) => f
+            Self::Const(generics, args, ret, body, _) => f
                 .debug_tuple("Const")
+                .field(generics)
                 .field(args)
                 .field(ret)
                 .field(&body.len())
                 .finish(),
-            Self::Mutable(args, ret, body) => f
+            Self::Mutable(generics, args, ret, body, ..) => f
                 .debug_tuple("Mutable")
+                .field(generics)
                 .field(args)
                 .field(ret)
                 .field(&body.len())
@@ -84,10 +165,15 @@ pub enum Value {
     I16(i16),
     F32(f32),
     Fun(ValueFun),
+    List(Vec<Value>),
+    Map(ValueMap),
     Object(ValueObject),
     Ref(AmvmVariable),
     String(String),
+    Tuple(Vec<Value>),
     U8(u8),
+    /// Sizes and indices
+    Usize(usize),
 }
 
 impl ValueObject {
@@ -109,6 +195,14 @@ impl ValueObject {
 }
 
 impl Value {
+    /// See [ValueFun::stored_in], other values don't change.
+    pub fn stored_in(self, context: &Arc<Mutex<Context>>) -> Self {
+        match self {
+            Self::Fun(fun) => Self::Fun(fun.stored_in(context)),
+            value => value,
+        }
+    }
+
     /// Convert any to string, cannot be overwrite
     pub fn to_string_or_default(&self) -> String {
         match self {
@@ -117,12 +211,13 @@ impl Value {
             Self::String(v) => v.clone(),
             Self::Bool(v) => format!("{v}"),
             Self::U8(v) => format!("{v}"),
+            Self::Usize(v) => format!("{v}"),
             Self::I16(v) => format!("{v}"),
             Self::F32(v) => format!("{v}"),
             Self::Fun(v) => match v {
                 ValueFun::Native(ref args, ret, _)
-                | ValueFun::Const(ref args, ret, _)
-                | ValueFun::Mutable(ref args, ret, _) => {
+                | ValueFun::Const(_, ref args, ret, ..)
+                | ValueFun::Mutable(_, ref args, ret, ..) => {
                     format!(
                         "[Function ({args}) {ret}]",
                         args = args
@@ -144,6 +239,43 @@ impl Value {
                 _ => todo!(),
             },
             Self::Ref(var) => var.read().to_string_or_default(),
+            Self::List(values) => format!(
+                "[{}]",
+                values
+                    .iter()
+                    .map(Value::to_string_or_default)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Self::Map(map) => format!(
+                "{{{}}}",
+                map.iter()
+                    .map(|(key, value)| format!(
+                        "{} => {}",
+                        key.to_value().to_string_or_default(),
+                        value.to_string_or_default()
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Self::Tuple(values) => format!(
+                "({})",
+                values
+                    .iter()
+                    .map(Value::to_string_or_default)
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// Position in a list, tuple or string.
+    pub fn as_index(&self) -> Option<usize> {
+        match self {
+            Self::U8(v) => Some(*v as usize),
+            Self::Usize(v) => Some(*v),
+            Self::Ref(var) => var.read().as_index(),
+            _ => None,
         }
     }
 
@@ -161,7 +293,8 @@ impl Value {
         use std::fmt::Write;
 
         let string = string.as_ref();
-        let len = string.len() as u8 as char;
+        // The parser reads chars, not bytes
+        let len = string.chars().count() as u8 as char;
         _ = buffer.write_char(len);
         _ = buffer.write_str(string);
 
@@ -240,16 +373,18 @@ impl Compilable for Value {
 
             Self::I16(v) => {
                 _ = buffer.write_char(VALUE_I16);
-                _ = buffer.write_str(&String::from_utf8_lossy(&[
-                    if v.is_positive() { 1 } else { 0 },
-                    (v.unsigned_abs() >> 8) as u8,
-                    v.unsigned_abs() as u8,
-                ]));
+                _ = buffer.write_char(if v.is_negative() { '\x00' } else { '\x01' });
+                _ = buffer.write_char((v.unsigned_abs() >> 8) as u8 as char);
+                _ = buffer.write_char(v.unsigned_abs() as u8 as char);
             }
             Self::U8(v) => {
                 _ = buffer.write_char(VALUE_U8);
                 _ = buffer.write_char(*v as char);
             }
+            Self::Usize(v) => {
+                _ = buffer.write_char(VALUE_USIZE);
+                buffer = v.compile_bytecode(buffer)?;
+            }
             Self::F32(v) => {
                 _ = buffer.write_char(VALUE_F32);
                 _ = buffer.write_str(&v.to_string());
@@ -257,12 +392,28 @@ impl Compilable for Value {
             }
 
             Self::Fun(_) => todo!(),
+            Self::List(values) => {
+                _ = buffer.write_char(VALUE_LIST);
+                buffer = Value::compile_slice(buffer, values)?;
+            }
+            Self::Map(map) => {
+                _ = buffer.write_char(VALUE_MAP);
+                let entries: Vec<(Value, Value)> = map
+                    .iter()
+                    .map(|(key, value)| (key.to_value(), value.clone()))
+                    .collect();
+                buffer = Value::compile_slice(buffer, &entries)?;
+            }
             Self::Object(_) => todo!(),
             Self::Ref(_) => unimplemented!("Reference cannot be compiled"),
             Self::String(string) => {
                 _ = buffer.write_char(VALUE_STRING);
                 buffer = string.compile_bytecode(buffer)?;
             }
+            Self::Tuple(values) => {
+                _ = buffer.write_char(VALUE_TUPLE);
+                buffer = Value::compile_slice(buffer, values)?;
+            }
         }
 
         Ok(buffer)
@@ -277,13 +428,54 @@ impl std::fmt::Display for Value {
             Self::Bool(v) => write!(f, "{v:?}"),
 
             Self::U8(v) => write!(f, "{v}u8"),
+            Self::Usize(v) => write!(f, "{v}usize"),
             Self::I16(v) => write!(f, "{v}i16"),
             Self::F32(v) => write!(f, "{v}f32"),
 
             Self::Fun(_) => f.write_str("[Function]"),
-            Self::Object(_) => f.write_str("[Native Object]"),
+            Self::List(values) => {
+                f.write_str("[")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(" ")?;
+                    }
+                    value.fmt(f)?;
+                }
+                f.write_str("]")
+            }
+            Self::Map(map) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, " {} => {value}", key.to_value())?;
+                }
+                f.write_str(" }")
+            }
+            // Fields are left out, only the type is shown
+            Self::Object(ValueObject::Instance(ty, fields)) => {
+                write!(f, "#{}", ty.name().unwrap_or("Anonymous"))?;
+                f.write_str(if fields.is_empty() { " {}" } else { " { … }" })
+            }
+            Self::Object(ValueObject::Variant(ty, variant, fields)) => {
+                write!(f, "#{}.{variant}", ty.name().unwrap_or("Anonymous"))?;
+                f.write_str(if fields.is_empty() { "" } else { " { … }" })
+            }
+            Self::Object(ValueObject::PropertyMap(_)) => f.write_str("{ … }"),
+            Self::Object(ValueObject::Native(_)) => f.write_str("[Native Object]"),
             Self::Ref(var) => write!(f, "&{}", var.read()),
             Self::String(v) => write!(f, "{v:?}"),
+            Self::Tuple(values) => {
+                f.write_str("(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    value.fmt(f)?;
+                }
+                f.write_str(")")
+            }
         }
     }
 }
@@ -325,6 +517,25 @@ impl Value {
         Ok((parser, value as u8))
     }
 
+    pub fn visit_usize(parser: Parser<'_>) -> ParserResult<'_, usize> {
+        let mut parser = parser;
+        let mut value: u64 = 0;
+        for _ in 0..8 {
+            let (parser_, b) = parser::anychar(parser)?;
+            parser = parser_;
+            value = (value << 8) + b as u64;
+        }
+
+        let value = usize::try_from(value).map_err(|_| {
+            parser.error(
+                parser::VerboseErrorKind::Context("Number doesn't fit in usize"),
+                true,
+            )
+        })?;
+
+        Ok((parser, value))
+    }
+
     pub fn visit_u16(parser: Parser<'_>) -> ParserResult<'_, u16> {
         let (parser, b1) = parser::anychar(parser)?;
         let (parser, b2) = parser::anychar(parser)?;
@@ -361,6 +572,13 @@ impl Value {
                 let (parser, b) = Value::visit_u8(parser)?;
                 (parser, Value::U8(b))
             }
+            b if b == VALUE_USIZE => {
+                let _tracing_span = tracing::trace_span!("usize");
+                let _tracing_span = _tracing_span.enter();
+
+                let (parser, v) = Value::visit_usize(parser)?;
+                (parser, Value::Usize(v))
+            }
             b if b == VALUE_I16 => {
                 let _tracing_span = tracing::trace_span!("i16");
                 let _tracing_span = _tracing_span.enter();
@@ -370,7 +588,7 @@ impl Value {
 
                 let (parser, num) = Value::visit_u16(parser)?;
 
-                let num = sign * num as i16;
+                let num = (num as i16).wrapping_mul(sign);
                 (parser, Value::I16(num))
             }
             // TODO: Rework this for safe parse and serialize.
@@ -442,6 +660,42 @@ impl Value {
 
                 (parser, Value::Char(char))
             }
+            b if b == VALUE_TUPLE => {
+                let _tracing_span = tracing::trace_span!("tuple");
+                let _tracing_span = _tracing_span.enter();
+
+                let (parser, values) = Value::visit_slice(parser, Value::visit)?;
+                (parser, Value::Tuple(values))
+            }
+            b if b == VALUE_LIST => {
+                let _tracing_span = tracing::trace_span!("list");
+                let _tracing_span = _tracing_span.enter();
+
+                let (parser, values) = Value::visit_slice(parser, Value::visit)?;
+                (parser, Value::List(values))
+            }
+            b if b == VALUE_MAP => {
+                let _tracing_span = tracing::trace_span!("map");
+                let _tracing_span = _tracing_span.enter();
+
+                let (parser, entries) = Value::visit_slice(parser, |parser| {
+                    let (parser, key) = Value::visit(parser)?;
+                    let (parser, value) = Value::visit(parser)?;
+
+                    Ok((parser, (key, value)))
+                })?;
+
+                let mut map = ValueMap::new();
+                for (key, value) in entries {
+                    let Some(key) = ValueMapKey::from_value(&key) else {
+                        return Err(parser
+                            .error(parser::VerboseErrorKind::Context("Invalid map key"), true));
+                    };
+                    map.insert(key, value);
+                }
+
+                (parser, Value::Map(map))
+            }
 
             b => {
                 return Err(parser::Err::Failure(parser::VerboseError {
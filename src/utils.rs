@@ -84,6 +84,19 @@ impl Compilable for u16 {
     }
 }
 
+/// Always 8 bytes, so the bytecode doesn't depend on the platform.
+impl Compilable for usize {
+    fn compile_bytecode(&self, mut buffer: String) -> CompileResult {
+        use std::fmt::Write;
+
+        for byte in (*self as u64).to_be_bytes() {
+            _ = buffer.write_char(byte as char);
+        }
+
+        Ok(buffer)
+    }
+}
+
 impl Compilable for String {
     fn compile_bytecode(&self, buffer: String) -> CompileResult {
         Value::compile_string(buffer, self)